@@ -0,0 +1,133 @@
+// Compares the Aho-Corasick and regex set matching layer with the previous approach of one regex
+// alternation per word list and one regex search per highlight rule. The source modules are included
+// directly, since log-viewer has no library target; their test modules are compiled but never run here.
+#![allow(dead_code, unused_imports)]
+
+#[path = "../src/constants.rs"]
+mod constants;
+#[path = "../src/line_filter.rs"]
+mod line_filter;
+#[path = "../src/matching.rs"]
+mod matching;
+#[path = "../src/word_pattern.rs"]
+mod word_pattern;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use regex::Regex;
+use std::hint::black_box;
+
+use line_filter::LineFilter;
+use matching::PatternSet;
+use word_pattern::build_word_pattern_from_strs;
+
+fn sample_lines() -> Vec<String> {
+    (0..2_000)
+        .map(|n| match n % 4 {
+            0 => format!("2024-05-01T12:00:{:02}Z web-{} GET /api/orders/{n} 200 took {}ms", n % 60, n % 8, n % 300),
+            1 => format!("2024-05-01T12:00:{:02}Z worker-{} job {n} finished user=u{}", n % 60, n % 5, n % 97),
+            2 => format!("2024-05-01T12:00:{:02}Z db-{} slow query took {}ms svc-{}", n % 60, n % 3, n, n % 500),
+            _ => format!("2024-05-01T12:00:{:02}Z web-{} ERROR upstream timeout noise-{}", n % 60, n % 8, n % 1000),
+        })
+        .collect()
+}
+
+// Distinct pseudo-random words, generated the same way on every run.
+fn words(count: usize) -> Vec<String> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    (0..count)
+        .map(|_| (0..5 + next() % 8).map(|_| char::from(b'a' + (next() % 26) as u8)).collect())
+        .collect()
+}
+
+fn bench_filter(c: &mut Criterion) {
+    let mut group = c.benchmark_group("filter");
+
+    for word_count in [10, 100, 1_000] {
+        let exclude_words = words(word_count);
+        // Every eighth line contains one of the exclude words.
+        let lines: Vec<_> = sample_lines()
+            .into_iter()
+            .enumerate()
+            .map(|(n, line)| match n % 8 {
+                0 => format!("{line} {}", exclude_words[n % word_count]),
+                _ => line,
+            })
+            .collect();
+        let bytes: usize = lines.iter().map(String::len).sum();
+        group.throughput(Throughput::Bytes(bytes as u64));
+
+        let include_words = vec!["GET".to_string(), "ERROR".to_string(), "query".to_string()];
+        let include_strs: Vec<_> = include_words.iter().map(String::as_str).collect();
+        let exclude_strs: Vec<_> = exclude_words.iter().map(String::as_str).collect();
+        let include_regex = build_word_pattern_from_strs(&include_strs).unwrap().unwrap();
+        let exclude_regex = build_word_pattern_from_strs(&exclude_strs).unwrap().unwrap();
+
+        group.bench_with_input(BenchmarkId::new("exclude_only/regex_alternation", word_count), &lines, |b, lines| {
+            b.iter(|| lines.iter().filter(|line| !exclude_regex.is_match(line)).count())
+        });
+        let filter = LineFilter::new(None, Some(exclude_words.clone()), Vec::new()).unwrap();
+        group.bench_with_input(BenchmarkId::new("exclude_only/word_set", word_count), &lines, |b, lines| {
+            b.iter(|| lines.iter().filter(|line| filter.allows(line)).count())
+        });
+
+        group.bench_with_input(BenchmarkId::new("include_exclude/regex_alternation", word_count), &lines, |b, lines| {
+            b.iter(|| {
+                lines
+                    .iter()
+                    .filter(|line| !exclude_regex.is_match(line) && include_regex.is_match(line))
+                    .count()
+            })
+        });
+        let filter = LineFilter::new(Some(include_words), Some(exclude_words), Vec::new()).unwrap();
+        group.bench_with_input(BenchmarkId::new("include_exclude/word_set", word_count), &lines, |b, lines| {
+            b.iter(|| lines.iter().filter(|line| filter.allows(line)).count())
+        });
+    }
+    group.finish();
+}
+
+fn bench_highlight_rules(c: &mut Criterion) {
+    let lines = sample_lines();
+    let bytes: usize = lines.iter().map(String::len).sum();
+    let mut group = c.benchmark_group("highlight_rules");
+    group.throughput(Throughput::Bytes(bytes as u64));
+
+    let common_rules = [Regex::new(r"\d+ms").unwrap(), Regex::new(r"user=\w+").unwrap()];
+    let service_rules: Vec<_> = (0..500).map(|n| Regex::new(&format!(r"\bsvc-{n}\b")).unwrap()).collect();
+
+    for rule_count in [10, 100, 500] {
+        let regexes: Vec<_> = service_rules[..rule_count].iter().chain(&common_rules).cloned().collect();
+
+        group.bench_with_input(BenchmarkId::new("regex_per_rule", rule_count), &lines, |b, lines| {
+            b.iter(|| {
+                lines
+                    .iter()
+                    .map(|line| regexes.iter().map(|regex| regex.find_iter(line).count()).sum::<usize>())
+                    .sum::<usize>()
+            })
+        });
+
+        let patterns = PatternSet::new(&regexes);
+        group.bench_with_input(BenchmarkId::new("pattern_set", rule_count), &lines, |b, lines| {
+            b.iter(|| {
+                lines
+                    .iter()
+                    .map(|line| {
+                        let matching = patterns.matching(black_box(line));
+                        matching.iter().map(|&index| regexes[index].find_iter(line).count()).sum::<usize>()
+                    })
+                    .sum::<usize>()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_filter, bench_highlight_rules);
+criterion_main!(benches);
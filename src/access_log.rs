@@ -0,0 +1,244 @@
+use anyhow::{bail, Result};
+use regex::Regex;
+use std::ops::Range;
+
+use crate::fields::Fields;
+use crate::line_highlighter::{Color, HighlightSpan, Style, TextStyle};
+
+const COMMON_LOG_FORMAT: &str = r#"$remote_addr - $remote_user [$time_local] "$request" $status $body_bytes_sent"#;
+const COMBINED_LOG_FORMAT: &str = concat!(
+    r#"$remote_addr - $remote_user [$time_local] "$request" $status $body_bytes_sent"#,
+    r#" "$http_referer" "$http_user_agent""#,
+);
+const NGINX_LOG_FORMAT: &str = concat!(
+    r#"$remote_addr - $remote_user [$time_local] "$request" $status $body_bytes_sent"#,
+    r#" "$http_referer" "$http_user_agent" "$http_x_forwarded_for""#,
+);
+
+#[derive(Clone)]
+pub struct AccessLogParser {
+    regex: Regex,
+    variables: Vec<String>,
+}
+
+impl AccessLogParser {
+    pub fn new(format: &str) -> Result<Self> {
+        let log_format = match format {
+            "common" => COMMON_LOG_FORMAT,
+            "combined" => COMBINED_LOG_FORMAT,
+            "nginx" => NGINX_LOG_FORMAT,
+            custom if custom.contains('$') => custom,
+            _ => bail!("Unknown log format: {format}, expected common, combined, nginx or a log_format string"),
+        };
+
+        compile_log_format(log_format)
+    }
+
+    pub fn parse(&self, line: &str, fields: &mut Fields) -> Option<Range<usize>> {
+        let captures = self.regex.captures(line.trim_end_matches(['\r', '\n']))?;
+        let mut status_span = None;
+
+        for (variable, capture) in self.variables.iter().zip(captures.iter().skip(1)) {
+            let Some(capture) = capture else {
+                continue;
+            };
+            let value = capture.as_str();
+            if value == "-" || value.is_empty() {
+                continue;
+            }
+
+            match variable.as_str() {
+                "request" => {
+                    let mut parts = value.splitn(3, ' ');
+                    for name in ["method", "path", "protocol"] {
+                        if let Some(part) = parts.next() {
+                            fields.insert(name, part);
+                        }
+                    }
+                }
+                "status" => {
+                    status_span = Some(capture.range());
+                    fields.insert("status", value);
+                }
+                other => fields.insert(field_name(other), value),
+            }
+        }
+
+        status_span
+    }
+
+    // The fields parse can set, in the order of the log format.
+    pub fn field_names(&self) -> impl Iterator<Item = &str> {
+        self.variables.iter().flat_map(|variable| match variable.as_str() {
+            "request" => vec!["method", "path", "protocol"],
+            other => vec![field_name(other)],
+        })
+    }
+}
+
+pub fn status_highlight(line: &str, range: Range<usize>) -> HighlightSpan {
+    let style = match line[range.clone()].as_bytes().first() {
+        Some(b'1' | b'2') => TextStyle::new(Color::Green, &[]),
+        Some(b'3') => TextStyle::new(Color::Cyan, &[]),
+        Some(b'4') => TextStyle::new(Color::Yellow, &[Style::Bold]),
+        _ => TextStyle::new(Color::BrightRed, &[Style::Bold]),
+    };
+
+    HighlightSpan { range, style }
+}
+
+fn field_name(variable: &str) -> &str {
+    match variable {
+        "body_bytes_sent" | "bytes_sent" => "bytes",
+        "http_referer" => "referrer",
+        "http_user_agent" => "user_agent",
+        "time_local" | "time_iso8601" => "time",
+        other => other,
+    }
+}
+
+fn compile_log_format(log_format: &str) -> Result<AccessLogParser> {
+    let mut pattern = String::from("^");
+    let mut variables = Vec::new();
+    let mut rest = log_format;
+
+    while let Some(start) = rest.find('$') {
+        pattern.push_str(&regex::escape(&rest[..start]));
+        rest = &rest[start + 1..];
+
+        let (variable, after) = match rest.strip_prefix('{') {
+            Some(braced) => match braced.split_once('}') {
+                Some((variable, after)) => (variable, after),
+                None => bail!("Unterminated ${{...}} variable in log format: {log_format}"),
+            },
+            None => {
+                let end = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                rest.split_at(end)
+            }
+        };
+        if variable.is_empty() {
+            bail!("Empty variable name in log format: {log_format}");
+        }
+
+        pattern.push_str(&match after.chars().next() {
+            Some('"') => r#"((?:[^"\\]|\\.)*)"#.to_string(),
+            Some(delimiter) => format!("([^{}]*)", regex::escape(&delimiter.to_string())),
+            None => r"(\S*)".to_string(),
+        });
+        variables.push(variable.to_string());
+        rest = after;
+    }
+    pattern.push_str(&regex::escape(rest));
+
+    if variables.is_empty() {
+        bail!("Log format has no $variables: {log_format}");
+    }
+
+    Ok(AccessLogParser {
+        regex: Regex::new(&pattern)?,
+        variables,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{status_highlight, AccessLogParser};
+    use crate::fields::Fields;
+    use crate::line_highlighter::Color;
+
+    const COMBINED_LINE: &str = r#"203.0.113.9 - alice [10/Oct/2024:13:55:36 +0000] "GET /api/users?id=1 HTTP/1.1" 503 2326 "https://example.com/" "Mozilla/5.0 (X11; Linux)""#;
+
+    fn parse(format: &str, line: &str) -> (Fields, Option<std::ops::Range<usize>>) {
+        let parser = AccessLogParser::new(format).unwrap();
+        let mut fields = Fields::default();
+        let status_span = parser.parse(line, &mut fields);
+        (fields, status_span)
+    }
+
+    #[test]
+    fn parses_combined_format() {
+        let (fields, status_span) = parse("combined", COMBINED_LINE);
+
+        assert_eq!(fields.get("remote_addr"), Some("203.0.113.9"));
+        assert_eq!(fields.get("remote_user"), Some("alice"));
+        assert_eq!(fields.get("time"), Some("10/Oct/2024:13:55:36 +0000"));
+        assert_eq!(fields.get("method"), Some("GET"));
+        assert_eq!(fields.get("path"), Some("/api/users?id=1"));
+        assert_eq!(fields.get("protocol"), Some("HTTP/1.1"));
+        assert_eq!(fields.get("status"), Some("503"));
+        assert_eq!(fields.get("bytes"), Some("2326"));
+        assert_eq!(fields.get("referrer"), Some("https://example.com/"));
+        assert_eq!(fields.get("user_agent"), Some("Mozilla/5.0 (X11; Linux)"));
+        assert_eq!(&COMBINED_LINE[status_span.unwrap()], "503");
+    }
+
+    #[test]
+    fn lists_field_names_in_log_format_order() {
+        let parser = AccessLogParser::new("common").unwrap();
+
+        assert_eq!(
+            parser.field_names().collect::<Vec<_>>(),
+            vec!["remote_addr", "remote_user", "time", "method", "path", "protocol", "status", "bytes"]
+        );
+    }
+
+    #[test]
+    fn parses_common_format_and_skips_placeholder_values() {
+        let (fields, _) = parse(
+            "common",
+            "127.0.0.1 - - [10/Oct/2024:13:55:36 +0000] \"POST /login HTTP/1.0\" 302 -\n",
+        );
+
+        assert_eq!(fields.get("remote_user"), None);
+        assert_eq!(fields.get("method"), Some("POST"));
+        assert_eq!(fields.get("status"), Some("302"));
+        assert_eq!(fields.get("bytes"), None);
+    }
+
+    #[test]
+    fn parses_nginx_default_format_with_escaped_quotes() {
+        let line = r#"10.0.0.1 - - [10/Oct/2024:13:55:36 +0000] "GET / HTTP/1.1" 200 612 "-" "curl \"8.0\"" "198.51.100.7""#;
+        let (fields, _) = parse("nginx", line);
+
+        assert_eq!(fields.get("user_agent"), Some(r#"curl \"8.0\""#));
+        assert_eq!(fields.get("http_x_forwarded_for"), Some("198.51.100.7"));
+    }
+
+    #[test]
+    fn parses_custom_log_format_strings() {
+        let (fields, _) = parse(
+            r#"$remote_addr [$time_iso8601] "$request" $status ${request_time}s"#,
+            r#"10.0.0.1 [2024-10-10T13:55:36+00:00] "DELETE /items/7 HTTP/2.0" 204 0.153s"#,
+        );
+
+        assert_eq!(fields.get("time"), Some("2024-10-10T13:55:36+00:00"));
+        assert_eq!(fields.get("method"), Some("DELETE"));
+        assert_eq!(fields.get("request_time"), Some("0.153"));
+    }
+
+    #[test]
+    fn colors_status_codes_by_class() {
+        let line = "200 302 404 503";
+
+        assert!(matches!(status_highlight(line, 0..3).style.foreground, Some(Color::Green)));
+        assert!(matches!(status_highlight(line, 4..7).style.foreground, Some(Color::Cyan)));
+        assert!(matches!(status_highlight(line, 8..11).style.foreground, Some(Color::Yellow)));
+        assert!(matches!(status_highlight(line, 12..15).style.foreground, Some(Color::BrightRed)));
+    }
+
+    #[test]
+    fn ignores_lines_that_do_not_match_format() {
+        let (fields, status_span) = parse("combined", "kernel: eth0 link up");
+
+        assert_eq!(fields, Fields::default());
+        assert!(status_span.is_none());
+    }
+
+    #[test]
+    fn rejects_unknown_formats() {
+        assert!(AccessLogParser::new("apache2").is_err());
+        assert!(AccessLogParser::new("${unterminated").is_err());
+    }
+}
@@ -0,0 +1,499 @@
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::Command;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::duration::parse_duration;
+use crate::severity::Severity;
+
+const ALERT_KEYS: &[&str] = &["name", "pattern", "level", "count", "window", "cooldown", "action"];
+const DEFAULT_ALERT_WINDOW: Duration = Duration::from_secs(60);
+const DEFAULT_ALERT_COOLDOWN: Duration = Duration::from_secs(60);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+pub enum AlertCondition {
+    Pattern(Regex),
+    Level(Severity),
+}
+
+impl AlertCondition {
+    fn matches(&self, line: &str) -> bool {
+        match self {
+            Self::Pattern(regex) => regex.is_match(line),
+            Self::Level(level) => Severity::detect(line).is_some_and(|severity| severity >= *level),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AlertAction {
+    Bell,
+    Notify,
+    Command(String),
+    Webhook(WebhookUrl),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebhookUrl {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl FromStr for WebhookUrl {
+    type Err = anyhow::Error;
+
+    fn from_str(url: &str) -> Result<Self> {
+        let Some(rest) = url.strip_prefix("http://") else {
+            bail!("Only http:// webhook URLs are supported: {url}");
+        };
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        let (host, port) = split_host_port(authority).with_context(|| format!("Invalid webhook URL: {url}"))?;
+        let port = match port {
+            Some(port) => port.parse().with_context(|| format!("Invalid webhook port: {url}"))?,
+            None => 80,
+        };
+        if host.is_empty() {
+            bail!("Missing webhook host: {url}");
+        }
+
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: if path.is_empty() { "/".to_string() } else { path.to_string() },
+        })
+    }
+}
+
+impl WebhookUrl {
+    fn host_header(&self) -> String {
+        let host = match self.host.contains(':') {
+            true => format!("[{}]", self.host),
+            false => self.host.clone(),
+        };
+        match self.port {
+            80 => host,
+            port => format!("{host}:{port}"),
+        }
+    }
+}
+
+// IPv6 hosts are written in brackets, as in http://[::1]:8080/, since they contain colons themselves.
+fn split_host_port(authority: &str) -> Result<(&str, Option<&str>)> {
+    let Some(bracketed) = authority.strip_prefix('[') else {
+        if authority.matches(':').count() > 1 {
+            bail!("IPv6 hosts must be enclosed in brackets");
+        }
+        return Ok(match authority.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        });
+    };
+
+    let (host, rest) = bracketed.split_once(']').context("Missing closing bracket after IPv6 host")?;
+    match rest {
+        "" => Ok((host, None)),
+        _ => match rest.strip_prefix(':') {
+            Some(port) => Ok((host, Some(port))),
+            None => bail!("Unexpected text after IPv6 host: {rest}"),
+        },
+    }
+}
+
+impl FromStr for AlertAction {
+    type Err = anyhow::Error;
+
+    fn from_str(action: &str) -> Result<Self> {
+        if let Some(command) = action.strip_prefix("exec:") {
+            return Ok(Self::Command(command.to_string()));
+        }
+        if let Some(url) = action.strip_prefix("webhook:") {
+            return Ok(Self::Webhook(url.parse()?));
+        }
+
+        match action {
+            "bell" => Ok(Self::Bell),
+            "notify" => Ok(Self::Notify),
+            _ => bail!("Unknown alert action: {action}, expected bell, notify, exec:CMD or webhook:URL"),
+        }
+    }
+}
+
+pub struct AlertRule {
+    pub name: String,
+    pub condition: AlertCondition,
+    pub threshold: usize,
+    pub window: Duration,
+    pub cooldown: Duration,
+    pub action: AlertAction,
+}
+
+impl FromStr for AlertRule {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let mut name = None;
+        let mut condition = None;
+        let mut threshold = 1;
+        let mut window = DEFAULT_ALERT_WINDOW;
+        let mut cooldown = DEFAULT_ALERT_COOLDOWN;
+        let mut action = AlertAction::Bell;
+
+        for (key, value) in split_alert_spec(spec)? {
+            match key {
+                "name" => name = Some(value.to_string()),
+                "pattern" => condition = Some(AlertCondition::Pattern(Regex::new(value)?)),
+                "level" => condition = Some(AlertCondition::Level(value.parse()?)),
+                "count" => threshold = value.parse().with_context(|| format!("Invalid alert count: {value}"))?,
+                "window" => window = parse_duration(value)?,
+                "cooldown" => cooldown = parse_duration(value)?,
+                "action" => action = value.parse()?,
+                _ => unreachable!("split_alert_spec only yields known keys"),
+            }
+        }
+
+        let Some(condition) = condition else {
+            bail!("Alert needs a pattern= or level= condition: {spec}");
+        };
+        if threshold == 0 {
+            bail!("Alert count must be at least 1: {spec}");
+        }
+
+        Ok(Self {
+            name: name.unwrap_or_else(|| spec.to_string()),
+            condition,
+            threshold,
+            window,
+            cooldown,
+            action,
+        })
+    }
+}
+
+// Values may contain commas (regex quantifiers, shell commands), so only a comma
+// followed by a known `key=` starts a new field.
+fn split_alert_spec(spec: &str) -> Result<Vec<(&str, &str)>> {
+    let mut boundaries = vec![0];
+    for (index, _) in spec.match_indices(',') {
+        let rest = &spec[index + 1..];
+        if ALERT_KEYS.iter().any(|key| rest.strip_prefix(key).is_some_and(|r| r.starts_with('='))) {
+            boundaries.push(index + 1);
+        }
+    }
+    boundaries.push(spec.len() + 1);
+
+    boundaries
+        .windows(2)
+        .map(|bounds| {
+            let field = &spec[bounds[0]..bounds[1] - 1];
+            match field.split_once('=') {
+                Some((key, value)) if ALERT_KEYS.contains(&key) => Ok((key, value)),
+                _ => bail!("Invalid alert field: {field}"),
+            }
+        })
+        .collect()
+}
+
+pub struct FiredAlert<'a> {
+    pub rule: &'a AlertRule,
+    pub line: String,
+    pub count: usize,
+}
+
+impl FiredAlert<'_> {
+    pub fn dispatch(&self) {
+        match &self.rule.action {
+            AlertAction::Bell => {
+                eprint!("\x07");
+                let _ = std::io::stderr().flush();
+            }
+            AlertAction::Notify => {
+                let mut command = Command::new("notify-send");
+                command.arg(format!("log-viewer: {}", self.rule.name)).arg(&self.line);
+                spawn_detached(command);
+            }
+            AlertAction::Command(shell_command) => spawn_detached(self.shell_command(shell_command)),
+            AlertAction::Webhook(url) => {
+                let url = url.clone();
+                let body = self.webhook_body();
+                thread::spawn(move || {
+                    if let Err(e) = post_webhook(&url, &body) {
+                        eprintln!("Error: Failed to deliver alert webhook: {e:?}");
+                    }
+                });
+            }
+        }
+    }
+
+    fn shell_command(&self, shell_command: &str) -> Command {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(shell_command)
+            .env("LOG_VIEWER_ALERT", &self.rule.name)
+            .env("LOG_VIEWER_LINE", &self.line)
+            .env("LOG_VIEWER_COUNT", self.count.to_string());
+        command
+    }
+
+    fn webhook_body(&self) -> String {
+        serde_json::json!({
+            "alert": self.rule.name,
+            "line": self.line,
+            "count": self.count,
+        })
+        .to_string()
+    }
+}
+
+fn spawn_detached(mut command: Command) {
+    match command.spawn() {
+        Ok(mut child) => {
+            thread::spawn(move || child.wait());
+        }
+        Err(e) => eprintln!("Error: Failed to run alert action: {e:?}"),
+    }
+}
+
+fn post_webhook(url: &WebhookUrl, body: &str) -> Result<()> {
+    let host = url.host_header();
+    let address = (url.host.as_str(), url.port)
+        .to_socket_addrs()
+        .with_context(|| format!("Failed to resolve {host}"))?
+        .next()
+        .with_context(|| format!("No address found for {host}"))?;
+    // A webhook that does not answer must not keep its thread around forever.
+    let mut stream =
+        TcpStream::connect_timeout(&address, WEBHOOK_TIMEOUT).with_context(|| format!("Failed to connect to {host}"))?;
+    stream.set_read_timeout(Some(WEBHOOK_TIMEOUT))?;
+    stream.set_write_timeout(Some(WEBHOOK_TIMEOUT))?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        url.path,
+        host,
+        body.len(),
+        body
+    )?;
+
+    Ok(())
+}
+
+struct AlertState {
+    rule: AlertRule,
+    hits: VecDeque<Instant>,
+    last_fired: Option<Instant>,
+}
+
+pub struct AlertEvaluator<C: Clock = SystemClock> {
+    states: Vec<AlertState>,
+    clock: C,
+}
+
+impl AlertEvaluator<SystemClock> {
+    pub fn new(rules: Vec<AlertRule>) -> Self {
+        Self::with_clock(rules, SystemClock)
+    }
+}
+
+impl<C: Clock> AlertEvaluator<C> {
+    pub fn with_clock(rules: Vec<AlertRule>, clock: C) -> Self {
+        Self {
+            states: rules
+                .into_iter()
+                .map(|rule| AlertState {
+                    rule,
+                    hits: VecDeque::new(),
+                    last_fired: None,
+                })
+                .collect(),
+            clock,
+        }
+    }
+
+    pub fn observe(&mut self, line: &str) -> Vec<FiredAlert<'_>> {
+        let now = self.clock.now();
+        let mut fired = Vec::new();
+
+        for state in &mut self.states {
+            if !state.rule.condition.matches(line) {
+                continue;
+            }
+
+            state.hits.push_back(now);
+            while state
+                .hits
+                .front()
+                .is_some_and(|&hit| now.duration_since(hit) > state.rule.window)
+            {
+                state.hits.pop_front();
+            }
+
+            let cooling_down = state
+                .last_fired
+                .is_some_and(|last_fired| now.duration_since(last_fired) < state.rule.cooldown);
+            if state.hits.len() >= state.rule.threshold && !cooling_down {
+                let count = state.hits.len();
+                state.hits.clear();
+                state.last_fired = Some(now);
+                fired.push(FiredAlert {
+                    rule: &state.rule,
+                    line: line.trim_end_matches(['\r', '\n']).to_string(),
+                    count,
+                });
+            }
+        }
+
+        fired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_host_port, AlertAction, AlertEvaluator, AlertRule, Clock, WebhookUrl};
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::time::{Duration, Instant};
+
+    #[derive(Clone)]
+    struct FakeClock {
+        now: Rc<Cell<Instant>>,
+    }
+
+    impl FakeClock {
+        fn new() -> Self {
+            Self {
+                now: Rc::new(Cell::new(Instant::now())),
+            }
+        }
+
+        fn advance(&self, by: Duration) {
+            self.now.set(self.now.get() + by);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            self.now.get()
+        }
+    }
+
+    fn fired_counts(evaluator: &mut AlertEvaluator<FakeClock>, line: &str) -> Vec<usize> {
+        evaluator.observe(line).iter().map(|alert| alert.count).collect()
+    }
+
+    #[test]
+    fn parses_alert_spec_with_commas_inside_values() {
+        let rule: AlertRule = r"pattern=\d{1,3} errors,count=3,window=30s,cooldown=5m,action=exec:echo a,b"
+            .parse()
+            .unwrap();
+
+        assert_eq!(rule.threshold, 3);
+        assert_eq!(rule.window, Duration::from_secs(30));
+        assert_eq!(rule.cooldown, Duration::from_secs(300));
+        assert_eq!(rule.action, AlertAction::Command("echo a,b".to_string()));
+        assert!(rule.condition.matches("12 errors"));
+    }
+
+    #[test]
+    fn rejects_invalid_alert_specs() {
+        assert!("count=3".parse::<AlertRule>().is_err());
+        assert!("pattern=x,count=0".parse::<AlertRule>().is_err());
+        assert!("bogus=1,pattern=x".parse::<AlertRule>().is_err());
+        assert!("pattern=x,action=siren".parse::<AlertRule>().is_err());
+    }
+
+    #[test]
+    fn parses_webhook_urls() {
+        assert_eq!(
+            "http://localhost:9000/hooks/log".parse::<WebhookUrl>().unwrap(),
+            WebhookUrl {
+                host: "localhost".to_string(),
+                port: 9000,
+                path: "/hooks/log".to_string(),
+            }
+        );
+        assert_eq!("http://127.0.0.1".parse::<WebhookUrl>().unwrap().path, "/");
+        assert!("https://example.com".parse::<WebhookUrl>().is_err());
+    }
+
+    #[test]
+    fn splits_webhook_hosts_and_ports() {
+        assert_eq!(split_host_port("example.com:8080").unwrap(), ("example.com", Some("8080")));
+        assert_eq!(split_host_port("example.com").unwrap(), ("example.com", None));
+        assert_eq!(split_host_port("[::1]:8080").unwrap(), ("::1", Some("8080")));
+        assert_eq!(split_host_port("[fe80::1]").unwrap(), ("fe80::1", None));
+        assert!(split_host_port("::1:8080").is_err());
+        assert!(split_host_port("[::1").is_err());
+        assert!(split_host_port("[::1]8080").is_err());
+
+        let url: WebhookUrl = "http://[::1]:8080/hook".parse().unwrap();
+        assert_eq!((url.host.as_str(), url.port), ("::1", 8080));
+        assert_eq!(url.host_header(), "[::1]:8080");
+        assert_eq!("http://localhost/".parse::<WebhookUrl>().unwrap().host_header(), "localhost");
+    }
+
+    #[test]
+    fn fires_when_threshold_is_reached_within_window() {
+        let clock = FakeClock::new();
+        let rule = "pattern=timeout,count=3,window=10s,cooldown=0s".parse().unwrap();
+        let mut evaluator = AlertEvaluator::with_clock(vec![rule], clock.clone());
+
+        assert!(fired_counts(&mut evaluator, "timeout").is_empty());
+        clock.advance(Duration::from_secs(11));
+        assert!(fired_counts(&mut evaluator, "timeout").is_empty());
+        assert!(fired_counts(&mut evaluator, "all good").is_empty());
+        clock.advance(Duration::from_secs(2));
+        assert!(fired_counts(&mut evaluator, "timeout").is_empty());
+        assert_eq!(fired_counts(&mut evaluator, "timeout"), vec![3]);
+    }
+
+    #[test]
+    fn respects_cooldown_between_alerts() {
+        let clock = FakeClock::new();
+        let rule = "level=error,cooldown=1m".parse().unwrap();
+        let mut evaluator = AlertEvaluator::with_clock(vec![rule], clock.clone());
+
+        assert_eq!(fired_counts(&mut evaluator, "ERROR db down"), vec![1]);
+        clock.advance(Duration::from_secs(30));
+        assert!(fired_counts(&mut evaluator, "FATAL db down").is_empty());
+        assert!(fired_counts(&mut evaluator, "WARN db slow").is_empty());
+        clock.advance(Duration::from_secs(31));
+        assert_eq!(fired_counts(&mut evaluator, "ERROR db down"), vec![2]);
+    }
+
+    #[test]
+    fn exposes_alert_details_to_shell_commands() {
+        let clock = FakeClock::new();
+        let rule = "name=db,pattern=down,action=exec:true".parse().unwrap();
+        let mut evaluator = AlertEvaluator::with_clock(vec![rule], clock);
+        let fired = evaluator.observe("db down\n");
+        let command = fired[0].shell_command("true");
+        let envs: Vec<_> = command
+            .get_envs()
+            .map(|(key, value)| (key.to_str().unwrap(), value.unwrap().to_str().unwrap()))
+            .collect();
+
+        assert!(envs.contains(&("LOG_VIEWER_ALERT", "db")));
+        assert!(envs.contains(&("LOG_VIEWER_LINE", "db down")));
+        assert!(envs.contains(&("LOG_VIEWER_COUNT", "1")));
+        assert_eq!(fired[0].webhook_body(), r#"{"alert":"db","count":1,"line":"db down"}"#);
+    }
+}
@@ -1,27 +1,151 @@
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{ArgGroup, CommandFactory, FromArgMatches, Parser};
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::time::Duration;
 
+use crate::color::ColorChoice;
 use crate::constants::DEFAULT_LOG_FILES;
+use crate::container_log::ContainerFormat;
+use crate::duration::parse_duration;
+use crate::listener::ListenAddress;
+use crate::output_format::OutputFormat;
+use crate::sampling::{MaxRate, SampleRate};
+use crate::severity::Severity;
+use crate::token_highlighter::TokenClass;
 
 #[derive(Debug, Parser)]
+#[command(group(ArgGroup::new("thinning").args(["sample", "max_rate"]).multiple(true)))]
 pub struct Args {
     #[arg(value_name = "LOG_FILES", num_args = 1.., default_values = DEFAULT_LOG_FILES)]
     pub log_files: Vec<String>,
-    #[arg(short = 'd', long = "disable-preset-excludes")]
-    pub disable_preset_excludes: bool,
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    #[arg(long = "profile", value_name = "NAME")]
+    pub profiles: Vec<String>,
+    #[arg(
+        short = 'd',
+        long = "disable-preset-excludes",
+        value_name = "PROFILE",
+        num_args = 0..,
+        require_equals = true,
+        value_delimiter = ','
+    )]
+    pub disable_preset_excludes: Option<Vec<String>>,
     #[arg(short, long, value_parser, num_args=1..)]
     pub exclude_words: Option<Vec<String>>,
     #[arg(short, long, value_parser, num_args=1..)]
     pub include_words: Option<Vec<String>>,
+    #[arg(long = "file-rule", value_name = "GLOB:OPTIONS")]
+    pub file_rules: Vec<String>,
+    #[arg(short = 'v', long, conflicts_with_all = ["explain", "show_excluded"])]
+    pub invert_match: bool,
+    #[arg(short = 'c', long, requires = "cat")]
+    pub count: bool,
+    #[arg(long, value_name = "N")]
+    pub max_count: Option<usize>,
+    #[arg(short = 'q', long, conflicts_with = "count")]
+    pub quiet: bool,
+    #[arg(long, value_name = "PATTERN")]
+    pub until_match: Option<String>,
+    #[arg(long, value_name = "DURATION", requires = "until_match", value_parser = parse_duration)]
+    pub timeout: Option<Duration>,
+    #[arg(long)]
+    pub explain: bool,
+    #[arg(long, conflicts_with = "explain")]
+    pub show_excluded: bool,
     #[arg(long)]
     pub debug: bool,
     #[arg(long)]
     pub cat: bool,
+    #[arg(long, value_name = "PATH", requires = "cat")]
+    pub state_file: Option<PathBuf>,
+    #[arg(long, requires = "cat")]
+    pub no_pager: bool,
+    #[arg(long, value_name = "PATH")]
+    pub tee: Option<PathBuf>,
+    #[arg(long)]
+    pub journal: bool,
+    #[arg(long = "unit", value_name = "UNIT", requires = "journal")]
+    pub units: Vec<String>,
+    #[arg(long, value_name = "PATH", requires = "journal")]
+    pub cursor_file: Option<PathBuf>,
+    #[arg(long, value_name = "COMMAND", conflicts_with = "journal")]
+    pub exec: Option<String>,
+    #[arg(long = "listen", value_name = "ADDRESS", conflicts_with_all = ["journal", "exec"])]
+    pub listen_addresses: Vec<ListenAddress>,
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "CLASS",
+        num_args = 0..,
+        require_equals = true,
+        value_delimiter = ','
+    )]
+    pub tokens: Option<Vec<TokenClass>>,
+    #[arg(long)]
+    pub color_stderr: bool,
+    #[arg(long)]
+    pub dedup: bool,
+    #[arg(long, value_name = "DURATION", default_value = "2s", value_parser = parse_duration)]
+    pub dedup_window: Duration,
+    #[arg(long, value_name = "PERCENT")]
+    pub sample: Option<SampleRate>,
+    #[arg(long, value_name = "LINES/PERIOD")]
+    pub max_rate: Option<MaxRate>,
+    #[arg(long, value_name = "LEVEL", requires = "thinning")]
+    pub keep_level: Option<Severity>,
+    #[arg(long = "alert", value_name = "SPEC")]
+    pub alerts: Vec<String>,
+    #[arg(long, value_name = "FORMAT")]
+    pub parser: Option<String>,
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub container_format: Option<ContainerFormat>,
+    #[arg(long)]
+    pub summary: bool,
+    #[arg(long = "extract", value_name = "PATTERN")]
+    pub extract_patterns: Vec<String>,
+    #[arg(long = "where", value_name = "CONDITION")]
+    pub where_conditions: Vec<String>,
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+    #[arg(long, value_delimiter = ',')]
+    pub columns: Vec<String>,
+    // Profiles only supply files when none were given on the command line.
+    #[arg(skip)]
+    pub log_files_given: bool,
+}
+
+impl Args {
+    pub fn parse_command_line() -> Self {
+        Self::parse_command_line_from(std::env::args_os())
+    }
+
+    fn parse_command_line_from<I, T>(args: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = Self::command().get_matches_from(args);
+        let mut args = Self::from_arg_matches(&matches)
+            .map_err(|e| e.format(&mut Self::command()))
+            .unwrap_or_else(|e| e.exit());
+        args.log_files_given = matches.value_source("log_files") == Some(ValueSource::CommandLine);
+        args
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::Args;
+    use crate::color::ColorChoice;
+    use crate::listener::ListenAddress;
+    use crate::severity::Severity;
+    use crate::token_highlighter::TokenClass;
     use clap::Parser;
+    use std::time::Duration;
 
     #[test]
     fn parses_default_log_file_when_none_is_provided() {
@@ -37,4 +161,252 @@ mod tests {
         assert!(args.cat);
         assert_eq!(args.log_files, vec!["/tmp/app.log", "/tmp/worker.log"]);
     }
+
+    #[test]
+    fn parses_config_path() {
+        let args = Args::parse_from(["log-viewer", "--config", "/etc/log-viewer.toml"]);
+
+        assert_eq!(args.config, Some(std::path::PathBuf::from("/etc/log-viewer.toml")));
+    }
+
+    #[test]
+    fn parses_profiles_and_disabled_preset_excludes() {
+        let args = Args::parse_from(["log-viewer", "--profile", "db", "--profile", "auth"]);
+        assert_eq!(args.profiles, vec!["db", "auth"]);
+        assert_eq!(args.disable_preset_excludes, None);
+
+        assert_eq!(Args::parse_from(["log-viewer", "-d"]).disable_preset_excludes, Some(Vec::new()));
+        let args = Args::parse_from(["log-viewer", "--disable-preset-excludes=default,db", "/tmp/app.log"]);
+        assert_eq!(args.disable_preset_excludes, Some(vec!["default".to_string(), "db".to_string()]));
+        assert_eq!(args.log_files, vec!["/tmp/app.log"]);
+    }
+
+    #[test]
+    fn records_whether_log_files_were_given() {
+        assert!(!Args::parse_command_line_from(["log-viewer"]).log_files_given);
+        assert!(Args::parse_command_line_from(["log-viewer", "/var/log/messages"]).log_files_given);
+    }
+
+    #[test]
+    fn parses_repeated_file_rules() {
+        let args = Args::parse_from([
+            "log-viewer",
+            "--file-rule",
+            "app.log:-e heartbeat",
+            "--file-rule=nginx/*.log:-i GET",
+            "/var/log/app.log",
+        ]);
+
+        assert_eq!(args.file_rules, vec!["app.log:-e heartbeat", "nginx/*.log:-i GET"]);
+        assert_eq!(args.log_files, vec!["/var/log/app.log"]);
+    }
+
+    #[test]
+    fn parses_grep_like_options() {
+        let args = Args::parse_from(["log-viewer", "--cat", "-v", "-c", "--max-count", "5", "/tmp/app.log"]);
+
+        assert!(args.invert_match);
+        assert!(args.count);
+        assert_eq!(args.max_count, Some(5));
+        assert!(Args::try_parse_from(["log-viewer", "--count"]).is_err());
+        assert!(Args::try_parse_from(["log-viewer", "-v", "--explain"]).is_err());
+    }
+
+    #[test]
+    fn parses_quiet_and_until_match() {
+        let args = Args::parse_from([
+            "log-viewer",
+            "-q",
+            "--until-match",
+            "Server started",
+            "--timeout",
+            "60s",
+            "/var/log/app.log",
+        ]);
+
+        assert!(args.quiet);
+        assert_eq!(args.until_match.as_deref(), Some("Server started"));
+        assert_eq!(args.timeout, Some(Duration::from_secs(60)));
+        assert!(Args::try_parse_from(["log-viewer", "--timeout", "60s"]).is_err());
+        assert!(Args::try_parse_from(["log-viewer", "--cat", "-q", "-c"]).is_err());
+    }
+
+    #[test]
+    fn parses_explain_modes() {
+        assert!(Args::parse_from(["log-viewer", "--explain"]).explain);
+        assert!(Args::parse_from(["log-viewer", "--show-excluded"]).show_excluded);
+        assert!(Args::try_parse_from(["log-viewer", "--explain", "--show-excluded"]).is_err());
+    }
+
+    #[test]
+    fn parses_state_file_in_cat_mode_only() {
+        let args = Args::parse_from(["log-viewer", "--cat", "--state-file", "/var/tmp/app.state", "/var/log/app.log"]);
+
+        assert_eq!(args.state_file, Some(std::path::PathBuf::from("/var/tmp/app.state")));
+        assert!(Args::try_parse_from(["log-viewer", "--state-file", "/var/tmp/app.state"]).is_err());
+    }
+
+    #[test]
+    fn parses_no_pager() {
+        assert!(Args::parse_from(["log-viewer", "--cat", "--no-pager"]).no_pager);
+        assert!(!Args::parse_from(["log-viewer", "--cat"]).no_pager);
+        assert!(Args::try_parse_from(["log-viewer", "--no-pager"]).is_err());
+    }
+
+    #[test]
+    fn parses_color_choice() {
+        assert_eq!(Args::parse_from(["log-viewer"]).color, ColorChoice::Auto);
+        assert_eq!(Args::parse_from(["log-viewer", "--color=never"]).color, ColorChoice::Never);
+        assert_eq!(Args::parse_from(["log-viewer", "--color", "always"]).color, ColorChoice::Always);
+        assert!(Args::try_parse_from(["log-viewer", "--color=sometimes"]).is_err());
+    }
+
+    #[test]
+    fn parses_token_classes() {
+        assert_eq!(Args::parse_from(["log-viewer"]).tokens, None);
+        assert_eq!(Args::parse_from(["log-viewer", "--tokens"]).tokens, Some(Vec::new()));
+        assert_eq!(
+            Args::parse_from(["log-viewer", "--tokens=ip,url", "/tmp/app.log"]).tokens,
+            Some(vec![TokenClass::Ip, TokenClass::Url])
+        );
+        assert!(Args::try_parse_from(["log-viewer", "--tokens=colors"]).is_err());
+    }
+
+    #[test]
+    fn parses_tee_file() {
+        let args = Args::parse_from(["log-viewer", "--tee", "/tmp/session.log", "/var/log/app.log"]);
+
+        assert_eq!(args.tee, Some(std::path::PathBuf::from("/tmp/session.log")));
+        assert_eq!(args.log_files, vec!["/var/log/app.log"]);
+    }
+
+    #[test]
+    fn parses_journal_options() {
+        let args = Args::parse_from([
+            "log-viewer",
+            "--journal",
+            "--unit",
+            "nginx.service",
+            "--unit",
+            "sshd.service",
+            "--cursor-file",
+            "/var/tmp/log-viewer.cursor",
+        ]);
+
+        assert!(args.journal);
+        assert_eq!(args.units, vec!["nginx.service", "sshd.service"]);
+        assert_eq!(args.cursor_file, Some(std::path::PathBuf::from("/var/tmp/log-viewer.cursor")));
+    }
+
+    #[test]
+    fn rejects_unit_without_journal() {
+        assert!(Args::try_parse_from(["log-viewer", "--unit", "nginx.service"]).is_err());
+    }
+
+    #[test]
+    fn parses_exec_command() {
+        let args = Args::parse_from(["log-viewer", "--exec", "cargo test", "--color-stderr", "-i", "FAILED"]);
+
+        assert_eq!(args.exec.as_deref(), Some("cargo test"));
+        assert!(args.color_stderr);
+        assert_eq!(args.include_words, Some(vec!["FAILED".to_string()]));
+        assert!(Args::try_parse_from(["log-viewer", "--exec", "true", "--journal"]).is_err());
+        let stop_early = ["log-viewer", "--exec", "serve", "--until-match", "ready", "--max-count", "1"];
+        assert!(Args::try_parse_from(stop_early).is_ok());
+    }
+
+    #[test]
+    fn parses_listen_addresses() {
+        let args = Args::parse_from([
+            "log-viewer",
+            "--listen",
+            "udp://0.0.0.0:5514",
+            "--listen",
+            "tcp://127.0.0.1:6000",
+        ]);
+
+        assert_eq!(
+            args.listen_addresses,
+            vec![
+                ListenAddress::Udp("0.0.0.0:5514".to_string()),
+                ListenAddress::Tcp("127.0.0.1:6000".to_string())
+            ]
+        );
+        assert!(Args::try_parse_from(["log-viewer", "--listen", "0.0.0.0:5514"]).is_err());
+    }
+
+    #[test]
+    fn parses_dedup_window() {
+        let args = Args::parse_from(["log-viewer", "--dedup", "--dedup-window", "500ms"]);
+
+        assert!(args.dedup);
+        assert_eq!(args.dedup_window, std::time::Duration::from_millis(500));
+    }
+
+    #[test]
+    fn parses_sampling_and_rate_limit() {
+        let args = Args::parse_from(["log-viewer", "--sample", "1%", "--max-rate", "200/s", "--keep-level", "warn"]);
+
+        assert_eq!(args.sample, Some("1%".parse().unwrap()));
+        assert_eq!(args.max_rate.unwrap().lines, 200);
+        assert_eq!(args.keep_level, Some(Severity::Warning));
+        assert!(Args::try_parse_from(["log-viewer", "--keep-level", "error"]).is_err());
+        assert!(Args::try_parse_from(["log-viewer", "--max-rate", "fast"]).is_err());
+    }
+
+    #[test]
+    fn parses_repeated_alerts() {
+        let args = Args::parse_from([
+            "log-viewer",
+            "--alert",
+            "pattern=timeout,count=5",
+            "--alert",
+            "level=error,action=notify",
+        ]);
+
+        assert_eq!(args.alerts, vec!["pattern=timeout,count=5", "level=error,action=notify"]);
+    }
+
+    #[test]
+    fn parses_access_log_parser_and_summary() {
+        let args = Args::parse_from([
+            "log-viewer",
+            "--cat",
+            "--parser",
+            "combined",
+            "--summary",
+            "/var/log/nginx/access.log",
+        ]);
+
+        assert_eq!(args.parser.as_deref(), Some("combined"));
+        assert!(args.summary);
+        assert_eq!(args.log_files, vec!["/var/log/nginx/access.log"]);
+    }
+
+    #[test]
+    fn parses_container_format() {
+        let args = Args::parse_from(["log-viewer", "--container-format", "docker"]);
+
+        assert_eq!(args.container_format, Some(crate::container_log::ContainerFormat::Docker));
+    }
+
+    #[test]
+    fn parses_extraction_and_output_options() {
+        let args = Args::parse_from([
+            "log-viewer",
+            "--extract",
+            r"(?P<latency>\d+)ms",
+            "--where",
+            "latency>250",
+            "--output",
+            "columns",
+            "--columns",
+            "latency,status",
+        ]);
+
+        assert_eq!(args.extract_patterns, vec![r"(?P<latency>\d+)ms"]);
+        assert_eq!(args.where_conditions, vec!["latency>250"]);
+        assert_eq!(args.output, crate::output_format::OutputFormat::Columns);
+        assert_eq!(args.columns, vec!["latency", "status"]);
+    }
 }
@@ -0,0 +1,56 @@
+use clap::ValueEnum;
+use std::env;
+use std::io::{self, IsTerminal};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn resolve(self) -> bool {
+        should_color(self, |name| env::var(name).ok(), io::stdout().is_terminal())
+    }
+}
+
+// See https://no-color.org and https://bixense.com/clicolors for the environment variables.
+fn should_color(choice: ColorChoice, env_var: impl Fn(&str) -> Option<String>, is_terminal: bool) -> bool {
+    let is_set = |name: &str| env_var(name).is_some_and(|value| !value.is_empty());
+
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto if is_set("NO_COLOR") => false,
+        ColorChoice::Auto if is_set("CLICOLOR_FORCE") && env_var("CLICOLOR_FORCE").as_deref() != Some("0") => true,
+        ColorChoice::Auto if env_var("CLICOLOR").as_deref() == Some("0") => false,
+        ColorChoice::Auto => is_terminal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{should_color, ColorChoice};
+
+    fn env_of(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        |name| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string())
+    }
+
+    #[test]
+    fn explicit_choice_wins_over_environment() {
+        assert!(should_color(ColorChoice::Always, env_of(&[("NO_COLOR", "1")]), false));
+        assert!(!should_color(ColorChoice::Never, env_of(&[("CLICOLOR_FORCE", "1")]), true));
+    }
+
+    #[test]
+    fn auto_follows_environment_then_terminal() {
+        assert!(should_color(ColorChoice::Auto, env_of(&[]), true));
+        assert!(!should_color(ColorChoice::Auto, env_of(&[]), false));
+        assert!(!should_color(ColorChoice::Auto, env_of(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")]), true));
+        assert!(should_color(ColorChoice::Auto, env_of(&[("NO_COLOR", "")]), true));
+        assert!(should_color(ColorChoice::Auto, env_of(&[("CLICOLOR_FORCE", "1")]), false));
+        assert!(!should_color(ColorChoice::Auto, env_of(&[("CLICOLOR_FORCE", "0")]), false));
+        assert!(!should_color(ColorChoice::Auto, env_of(&[("CLICOLOR", "0")]), true));
+    }
+}
@@ -1,23 +1,112 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
 use crate::cli::Args;
+use crate::color::ColorChoice;
+use crate::container_log::ContainerFormat;
+use crate::line_pipeline::ExplainMode;
+use crate::listener::ListenAddress;
+use crate::output_format::OutputFormat;
+use crate::sampling::{MaxRate, SampleRate};
+use crate::severity::Severity;
+use crate::token_highlighter::TokenClass;
 
 pub struct AppConfig {
     pub log_files: Vec<String>,
-    pub disable_preset_excludes: bool,
+    pub config_file: Option<PathBuf>,
+    pub log_files_given: bool,
+    pub profiles: Vec<String>,
+    pub disabled_preset_excludes: Option<Vec<String>>,
     pub exclude_words: Option<Vec<String>>,
     pub include_words: Option<Vec<String>>,
+    pub file_rules: Vec<String>,
+    pub explain_mode: Option<ExplainMode>,
+    pub invert_match: bool,
+    pub count_only: bool,
+    pub max_count: Option<usize>,
+    pub quiet: bool,
+    pub until_match: Option<String>,
+    pub timeout: Option<Duration>,
     pub debug: bool,
     pub use_cat_mode: bool,
+    pub state_file: Option<PathBuf>,
+    pub use_pager: bool,
+    pub buffer_output: bool,
+    pub tee_file: Option<PathBuf>,
+    pub use_keyboard_controls: bool,
+    pub use_journal: bool,
+    pub journal_units: Vec<String>,
+    pub journal_cursor_file: Option<PathBuf>,
+    pub exec_command: Option<String>,
+    pub listen_addresses: Vec<ListenAddress>,
+    pub color: ColorChoice,
+    pub token_classes: Option<Vec<TokenClass>>,
+    pub color_stderr: bool,
+    pub dedup_window: Option<Duration>,
+    pub sample_rate: Option<SampleRate>,
+    pub max_rate: Option<MaxRate>,
+    pub keep_level: Option<Severity>,
+    pub alerts: Vec<String>,
+    pub parser: Option<String>,
+    pub container_format: Option<ContainerFormat>,
+    pub summary: bool,
+    pub extract_patterns: Vec<String>,
+    pub where_conditions: Vec<String>,
+    pub output_format: OutputFormat,
+    pub columns: Vec<String>,
 }
 
 impl From<Args> for AppConfig {
     fn from(args: Args) -> Self {
         Self {
             log_files: args.log_files,
-            disable_preset_excludes: args.disable_preset_excludes,
+            config_file: args.config,
+            log_files_given: args.log_files_given,
+            profiles: args.profiles,
+            disabled_preset_excludes: args.disable_preset_excludes,
             exclude_words: args.exclude_words,
             include_words: args.include_words,
+            file_rules: args.file_rules,
+            invert_match: args.invert_match,
+            count_only: args.count,
+            max_count: args.max_count,
+            quiet: args.quiet,
+            until_match: args.until_match,
+            timeout: args.timeout,
+            explain_mode: match (args.explain, args.show_excluded) {
+                (true, _) => Some(ExplainMode::All),
+                (_, true) => Some(ExplainMode::ExcludedOnly),
+                _ => None,
+            },
             debug: args.debug,
             use_cat_mode: args.cat,
+            state_file: args.state_file,
+            // Only cat mode output has an end; --exec and --listen always follow.
+            use_pager: args.cat && !args.no_pager && args.exec.is_none() && args.listen_addresses.is_empty(),
+            buffer_output: args.cat && args.exec.is_none() && args.listen_addresses.is_empty(),
+            tee_file: args.tee,
+            // --exec leaves the terminal settings to the command; all other modes without --cat follow their input.
+            use_keyboard_controls: (!args.cat || !args.listen_addresses.is_empty()) && args.exec.is_none(),
+            use_journal: args.journal,
+            journal_units: args.units,
+            journal_cursor_file: args.cursor_file,
+            exec_command: args.exec,
+            listen_addresses: args.listen_addresses,
+            color: args.color,
+            token_classes: args.tokens,
+            color_stderr: args.color_stderr,
+            dedup_window: args.dedup.then_some(args.dedup_window),
+            sample_rate: args.sample,
+            max_rate: args.max_rate,
+            keep_level: args.keep_level,
+            alerts: args.alerts,
+            parser: args.parser,
+            container_format: args.container_format,
+            summary: args.summary,
+            extract_patterns: args.extract_patterns,
+            where_conditions: args.where_conditions,
+            output_format: args.output,
+            columns: args.columns,
         }
     }
 }
@@ -0,0 +1,318 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::constants::PRESET_EXCLUDE_WORDS;
+use crate::file_rule::FileRule;
+use crate::line_filter::{FilterWord, WordOrigin};
+use crate::line_highlighter::HighlightRule;
+use crate::token_highlighter::TokenClass;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(default)]
+    pub highlight: Vec<HighlightConfig>,
+    #[serde(default)]
+    pub tokens: Vec<TokenClass>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    #[serde(default)]
+    pub file_rules: BTreeMap<String, FileRuleConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HighlightConfig {
+    pub pattern: String,
+    pub style: String,
+    #[serde(default = "default_scope")]
+    pub scope: String,
+}
+
+impl HighlightConfig {
+    pub fn rule(&self) -> Result<HighlightRule> {
+        HighlightRule::new(&self.pattern, &self.style, &self.scope)
+    }
+}
+
+// The exclude words of a profile are its preset excludes, which --disable-preset-excludes can turn off.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(default)]
+    pub include_words: Vec<String>,
+    #[serde(default)]
+    pub exclude_words: Vec<String>,
+    #[serde(default)]
+    pub highlight: Vec<HighlightConfig>,
+    #[serde(default)]
+    pub files: Vec<String>,
+    pub parser: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileRuleConfig {
+    #[serde(default)]
+    pub include_words: Vec<String>,
+    #[serde(default)]
+    pub exclude_words: Vec<String>,
+    #[serde(default)]
+    pub highlight: Vec<HighlightConfig>,
+}
+
+impl Profile {
+    pub const DEFAULT: &'static str = "default";
+}
+
+// The merged profiles; filter words remember which profile they came from.
+#[derive(Debug, Default)]
+pub struct SelectedProfiles {
+    pub include_words: Vec<FilterWord>,
+    pub preset_exclude_words: Vec<FilterWord>,
+    pub highlight: Vec<HighlightConfig>,
+    pub files: Vec<String>,
+    pub parser: Option<String>,
+}
+
+impl SelectedProfiles {
+    pub fn highlight_rules(&self) -> Result<Vec<HighlightRule>> {
+        self.highlight.iter().map(HighlightConfig::rule).collect()
+    }
+
+    fn merge(&mut self, name: &str, profile: &Profile, keep_excludes: bool) -> Result<()> {
+        let origin = WordOrigin::Profile(name.to_string());
+        let words = |words: &[String]| -> Vec<FilterWord> {
+            words.iter().map(|word| FilterWord::new(word, origin.clone())).collect()
+        };
+        self.include_words.extend(words(&profile.include_words));
+        if keep_excludes {
+            self.preset_exclude_words.extend(words(&profile.exclude_words));
+        }
+        self.highlight.extend(profile.highlight.iter().cloned());
+        self.files.extend(profile.files.iter().cloned());
+
+        match (&self.parser, &profile.parser) {
+            (Some(parser), Some(other)) if parser != other => {
+                bail!("Profile {name} uses parser {other}, but another selected profile uses {parser}")
+            }
+            (None, Some(other)) => self.parser = Some(other.clone()),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl ConfigFile {
+    // An explicitly given config file must exist; the default one is optional.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+
+        let contents =
+            fs::read_to_string(&path).with_context(|| format!("Failed to read config file: {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("Failed to parse config file: {}", path.display()))
+    }
+
+    pub fn highlight_rules(&self) -> Result<Vec<HighlightRule>> {
+        self.highlight.iter().map(HighlightConfig::rule).collect()
+    }
+
+    pub fn file_rules(&self) -> Result<Vec<FileRule>> {
+        self.file_rules
+            .iter()
+            .map(|(glob, config)| {
+                let mut file_rule = FileRule::new(glob)?;
+                file_rule.include_words = config.include_words.clone();
+                file_rule.exclude_words = config.exclude_words.clone();
+                file_rule.highlight = config.highlight.clone();
+                Ok(file_rule)
+            })
+            .collect()
+    }
+
+    // The default profile is always selected and can be redefined in the config file. Without names,
+    // disabled_excludes turns off the preset excludes of every selected profile.
+    pub fn select_profiles(&self, names: &[String], disabled_excludes: Option<&[String]>) -> Result<SelectedProfiles> {
+        let mut selected = vec![Profile::DEFAULT];
+        for name in names {
+            if !selected.contains(&name.as_str()) {
+                selected.push(name);
+            }
+        }
+
+        let builtin_default = builtin_default_profile();
+        let mut merged = SelectedProfiles::default();
+        for &name in &selected {
+            let profile = match self.profiles.get(name) {
+                Some(profile) => profile,
+                None if name == Profile::DEFAULT => &builtin_default,
+                None => bail!("Unknown profile: {name}"),
+            };
+            let keep_excludes = match disabled_excludes {
+                None => true,
+                Some([]) => false,
+                Some(disabled) => !disabled.iter().any(|disabled| disabled == name),
+            };
+            merged.merge(name, profile, keep_excludes)?;
+        }
+
+        for name in disabled_excludes.unwrap_or_default() {
+            if !selected.contains(&name.as_str()) {
+                bail!("Cannot disable preset excludes of unselected profile: {name}");
+            }
+        }
+
+        Ok(merged)
+    }
+}
+
+fn builtin_default_profile() -> Profile {
+    Profile {
+        exclude_words: PRESET_EXCLUDE_WORDS.iter().map(|&s| s.to_string()).collect(),
+        ..Profile::default()
+    }
+}
+
+fn default_scope() -> String {
+    "match".to_string()
+}
+
+fn default_path() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_dir.join("log-viewer").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConfigFile;
+    use crate::line_filter::FilterWord;
+    use crate::line_highlighter::HighlightScope;
+    use crate::token_highlighter::TokenClass;
+
+    #[test]
+    fn parses_highlight_rules() {
+        let config: ConfigFile = toml::from_str(
+            r##"
+            tokens = ["ip", "url"]
+
+            [[highlight]]
+            pattern = 'user=\w+'
+            style = "bold underline #ff8800 on black"
+
+            [[highlight]]
+            pattern = "timeout"
+            style = "208"
+            scope = "line"
+            "##,
+        )
+        .unwrap();
+
+        let rules = config.highlight_rules().unwrap();
+        assert_eq!(rules.len(), 2);
+        assert!(rules[0].regex.is_match("user=alice"));
+        assert_eq!(rules[0].scope, HighlightScope::Match);
+        assert_eq!(rules[1].scope, HighlightScope::Line);
+        assert_eq!(config.tokens, vec![TokenClass::Ip, TokenClass::Url]);
+        assert!(toml::from_str::<ConfigFile>("[[highlight]]\npattern = \"x\"").is_err());
+    }
+
+    fn words(words: &[FilterWord]) -> Vec<&str> {
+        words.iter().map(|word| word.word.as_str()).collect()
+    }
+
+    #[test]
+    fn merges_selected_profiles_with_the_default() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            [profiles.nginx]
+            exclude_words = ["healthcheck"]
+            files = ["/var/log/nginx/access.log"]
+            parser = "combined"
+
+            [profiles.auth]
+            include_words = ["sshd"]
+            exclude_words = ["session opened"]
+
+            [[profiles.auth.highlight]]
+            pattern = "Failed password"
+            style = "red"
+            "#,
+        )
+        .unwrap();
+        let names = vec!["nginx".to_string(), "auth".to_string()];
+
+        let profile = config.select_profiles(&names, None).unwrap();
+        assert_eq!(words(&profile.include_words), vec!["sshd"]);
+        assert_eq!(
+            words(&profile.preset_exclude_words),
+            vec!["aaa", "bbb", "ccc", "healthcheck", "session opened"]
+        );
+        assert_eq!(profile.preset_exclude_words[3].to_string(), r#""healthcheck" from preset of profile nginx"#);
+        assert_eq!(profile.files, vec!["/var/log/nginx/access.log"]);
+        assert_eq!(profile.parser.as_deref(), Some("combined"));
+        assert_eq!(profile.highlight_rules().unwrap().len(), 1);
+
+        let disabled = vec!["default".to_string(), "auth".to_string()];
+        let profile = config.select_profiles(&names, Some(&disabled)).unwrap();
+        assert_eq!(words(&profile.preset_exclude_words), vec!["healthcheck"]);
+        assert!(config.select_profiles(&names, Some(&[])).unwrap().preset_exclude_words.is_empty());
+
+        assert!(config.select_profiles(&["db".to_string()], None).is_err());
+        assert!(config.select_profiles(&[], Some(&disabled)).is_err());
+    }
+
+    #[test]
+    fn config_file_can_redefine_the_default_profile() {
+        let config: ConfigFile = toml::from_str("[profiles.default]\nexclude_words = [\"DEBUG\"]").unwrap();
+
+        assert_eq!(words(&config.select_profiles(&[], None).unwrap().preset_exclude_words), vec!["DEBUG"]);
+        assert_eq!(
+            words(&ConfigFile::default().select_profiles(&[], None).unwrap().preset_exclude_words),
+            vec!["aaa", "bbb", "ccc"]
+        );
+    }
+
+    #[test]
+    fn parses_file_rules_keyed_by_glob() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            [file_rules."app.log"]
+            exclude_words = ["heartbeat"]
+
+            [file_rules."nginx/*.log"]
+            include_words = ["GET"]
+
+            [[file_rules."nginx/*.log".highlight]]
+            pattern = ' 5\d\d '
+            style = "red"
+            "#,
+        )
+        .unwrap();
+
+        let file_rules = config.file_rules().unwrap();
+        assert_eq!(file_rules.len(), 2);
+        assert_eq!(file_rules[0].exclude_words, vec!["heartbeat"]);
+        assert!(file_rules[1].matches(std::path::Path::new("/var/log/nginx/access.log")));
+        assert_eq!(file_rules[1].highlight.len(), 1);
+    }
+
+    #[test]
+    fn explicit_config_file_must_exist() {
+        let path = std::env::temp_dir().join("log-viewer-missing-config.toml");
+        assert!(ConfigFile::load(Some(&path)).is_err());
+    }
+}
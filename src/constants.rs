@@ -1,4 +1,17 @@
+#[cfg(test)]
+use crate::line_filter::{FilterWord, WordOrigin};
+
 pub const PRESET_EXCLUDE_WORDS: &[&str] = &["aaa", "bbb", "ccc"];
+
+// The preset exclude words as the default profile gives them to the filter.
+#[cfg(test)]
+pub fn preset_words() -> Vec<FilterWord> {
+    PRESET_EXCLUDE_WORDS
+        .iter()
+        .map(|word| FilterWord::new(word, WordOrigin::Profile("default".to_string())))
+        .collect()
+}
+
 pub const DEFAULT_LOG_FILES: &[&str] = &["/var/log/messages"];
 
 pub const CRITICAL_WORDS: &[&str] = &["foo", "bar"];
@@ -0,0 +1,268 @@
+use clap::ValueEnum;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::fields::Fields;
+
+const CONTAINER_LOG_DIRS: &[&str] = &["/var/log/containers", "/var/log/pods", "/var/lib/docker/containers"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ContainerFormat {
+    Auto,
+    Cri,
+    Docker,
+}
+
+impl ContainerFormat {
+    pub fn for_path(path: &Path) -> Option<Self> {
+        CONTAINER_LOG_DIRS
+            .iter()
+            .any(|dir| path.starts_with(dir))
+            .then_some(Self::Auto)
+    }
+}
+
+pub struct ContainerLogEntry {
+    pub message: String,
+    pub fields: Fields,
+}
+
+struct Fragment<'a> {
+    time: &'a str,
+    stream: &'a str,
+    message: &'a str,
+    partial: bool,
+}
+
+struct PendingEntry {
+    time: String,
+    message: String,
+}
+
+pub struct ContainerLogDecoder {
+    format: ContainerFormat,
+    pending: HashMap<String, PendingEntry>,
+}
+
+impl ContainerLogDecoder {
+    pub fn new(format: ContainerFormat) -> Self {
+        Self {
+            format,
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn decode(&mut self, line: &str) -> Option<ContainerLogEntry> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let docker_line = match self.format {
+            ContainerFormat::Cri => None,
+            ContainerFormat::Docker | ContainerFormat::Auto => serde_json::from_str::<Value>(line).ok(),
+        };
+
+        let fragment = match (self.format, &docker_line) {
+            (ContainerFormat::Docker | ContainerFormat::Auto, Some(value)) => parse_docker_line(value),
+            (ContainerFormat::Cri | ContainerFormat::Auto, _) => parse_cri_line(line),
+            (ContainerFormat::Docker, None) => None,
+        };
+
+        let Some(fragment) = fragment else {
+            return Some(ContainerLogEntry {
+                message: line.to_string(),
+                fields: Fields::default(),
+            });
+        };
+
+        self.reassemble(fragment)
+    }
+
+    fn reassemble(&mut self, fragment: Fragment) -> Option<ContainerLogEntry> {
+        if fragment.partial {
+            self.pending
+                .entry(fragment.stream.to_string())
+                .or_insert_with(|| PendingEntry {
+                    time: fragment.time.to_string(),
+                    message: String::new(),
+                })
+                .message
+                .push_str(fragment.message);
+            return None;
+        }
+
+        let (time, message) = match self.pending.remove(fragment.stream) {
+            Some(mut pending) => {
+                pending.message.push_str(fragment.message);
+                (pending.time, pending.message)
+            }
+            None => (fragment.time.to_string(), fragment.message.to_string()),
+        };
+
+        Some(log_entry(fragment.stream, time, message))
+    }
+
+    // Hands out the partial lines still waiting for their last fragment, e.g. when a file ends in the
+    // middle of a line, so they are shown as they are rather than lost.
+    pub fn finish(&mut self) -> Vec<ContainerLogEntry> {
+        let mut pending: Vec<_> = self.pending.drain().collect();
+        pending.sort_by(|(stream, _), (other, _)| stream.cmp(other));
+
+        pending
+            .into_iter()
+            .map(|(stream, pending)| log_entry(&stream, pending.time, pending.message))
+            .collect()
+    }
+}
+
+fn log_entry(stream: &str, time: String, message: String) -> ContainerLogEntry {
+    let mut fields = Fields::default();
+    fields.insert("stream", stream);
+    fields.insert("time", time);
+
+    ContainerLogEntry { message, fields }
+}
+
+fn parse_cri_line(line: &str) -> Option<Fragment<'_>> {
+    let mut parts = line.splitn(4, ' ');
+    let time = parts.next()?;
+    let stream = parts.next().filter(|stream| matches!(*stream, "stdout" | "stderr"))?;
+    let partial = match parts.next()?.split(':').next()? {
+        "P" => true,
+        "F" => false,
+        _ => return None,
+    };
+
+    Some(Fragment {
+        time,
+        stream,
+        message: parts.next().unwrap_or_default(),
+        partial,
+    })
+}
+
+fn parse_docker_line(value: &Value) -> Option<Fragment<'_>> {
+    let log = value.get("log")?.as_str()?;
+    let message = log.strip_suffix('\n');
+
+    Some(Fragment {
+        time: value.get("time").and_then(Value::as_str).unwrap_or_default(),
+        stream: value.get("stream").and_then(Value::as_str).unwrap_or("stdout"),
+        message: message.unwrap_or(log),
+        partial: message.is_none(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContainerFormat, ContainerLogDecoder};
+    use std::path::Path;
+
+    fn decode(decoder: &mut ContainerLogDecoder, line: &str) -> Option<(String, Option<String>, Option<String>)> {
+        decoder.decode(line).map(|entry| {
+            (
+                entry.message,
+                entry.fields.get("stream").map(str::to_string),
+                entry.fields.get("time").map(str::to_string),
+            )
+        })
+    }
+
+    #[test]
+    fn unwraps_cri_lines() {
+        let mut decoder = ContainerLogDecoder::new(ContainerFormat::Cri);
+
+        assert_eq!(
+            decode(&mut decoder, "2024-01-01T00:00:00.123Z stderr F panic: boom\n"),
+            Some((
+                "panic: boom".to_string(),
+                Some("stderr".to_string()),
+                Some("2024-01-01T00:00:00.123Z".to_string())
+            ))
+        );
+    }
+
+    #[test]
+    fn reassembles_partial_cri_lines_per_stream() {
+        let mut decoder = ContainerLogDecoder::new(ContainerFormat::Cri);
+
+        assert!(decode(&mut decoder, "2024-01-01T00:00:00Z stdout P first ").is_none());
+        assert_eq!(
+            decode(&mut decoder, "2024-01-01T00:00:01Z stderr F other stream"),
+            Some((
+                "other stream".to_string(),
+                Some("stderr".to_string()),
+                Some("2024-01-01T00:00:01Z".to_string())
+            ))
+        );
+        assert!(decode(&mut decoder, "2024-01-01T00:00:02Z stdout P second ").is_none());
+        assert_eq!(
+            decode(&mut decoder, "2024-01-01T00:00:03Z stdout F third"),
+            Some((
+                "first second third".to_string(),
+                Some("stdout".to_string()),
+                Some("2024-01-01T00:00:00Z".to_string())
+            ))
+        );
+    }
+
+    #[test]
+    fn finish_returns_unterminated_partial_lines() {
+        let mut decoder = ContainerLogDecoder::new(ContainerFormat::Auto);
+
+        assert!(decode(&mut decoder, "2024-01-01T00:00:00Z stdout P cut ").is_none());
+        assert!(decode(&mut decoder, "2024-01-01T00:00:01Z stdout P off").is_none());
+        assert!(decode(&mut decoder, r#"{"log":"no newline","stream":"stderr","time":"t"}"#).is_none());
+
+        let finished: Vec<_> = decoder
+            .finish()
+            .into_iter()
+            .map(|entry| (entry.message, entry.fields.get("time").map(str::to_string)))
+            .collect();
+        assert_eq!(
+            finished,
+            vec![
+                ("no newline".to_string(), Some("t".to_string())),
+                ("cut off".to_string(), Some("2024-01-01T00:00:00Z".to_string()))
+            ]
+        );
+        assert!(decoder.finish().is_empty());
+    }
+
+    #[test]
+    fn unwraps_and_reassembles_docker_json_lines() {
+        let mut decoder = ContainerLogDecoder::new(ContainerFormat::Docker);
+
+        assert!(decode(&mut decoder, r#"{"log":"long ","stream":"stdout","time":"2024-01-01T00:00:00Z"}"#).is_none());
+        assert_eq!(
+            decode(&mut decoder, r#"{"log":"line \"quoted\"\n","stream":"stdout","time":"2024-01-01T00:00:01Z"}"#),
+            Some((
+                r#"long line "quoted""#.to_string(),
+                Some("stdout".to_string()),
+                Some("2024-01-01T00:00:00Z".to_string())
+            ))
+        );
+    }
+
+    #[test]
+    fn auto_detects_format_and_passes_other_lines_through() {
+        let mut decoder = ContainerLogDecoder::new(ContainerFormat::Auto);
+
+        assert_eq!(
+            decode(&mut decoder, r#"{"log":"from docker\n","stream":"stderr","time":"t"}"#).unwrap().0,
+            "from docker"
+        );
+        assert_eq!(decode(&mut decoder, "t stdout F from cri").unwrap().0, "from cri");
+        assert_eq!(
+            decode(&mut decoder, "plain application line\n"),
+            Some(("plain application line".to_string(), None, None))
+        );
+    }
+
+    #[test]
+    fn selects_auto_format_for_container_log_directories() {
+        assert_eq!(
+            ContainerFormat::for_path(Path::new("/var/log/containers/app_default_web-1.log")),
+            Some(ContainerFormat::Auto)
+        );
+        assert_eq!(ContainerFormat::for_path(Path::new("/var/log/messages")), None);
+    }
+}
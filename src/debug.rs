@@ -1,7 +1,8 @@
 use colored::Colorize;
-use regex::Regex;
 
-pub fn print_debug_info(log_files: &[String], include_regex: &Option<Regex>, exclude_regex: &Option<Regex>) {
+use crate::line_filter::FilterWord;
+
+pub fn print_debug_info(log_files: &[String], include_words: &[FilterWord], exclude_words: &[FilterWord]) {
     println!();
     println!("{}", "=".repeat(40).cyan());
     println!("{}", "  DEBUG INFO".bold().cyan());
@@ -10,18 +11,19 @@ pub fn print_debug_info(log_files: &[String], include_regex: &Option<Regex>, exc
     // Log files
     println!("{}: {}", "Log files".bold(), log_files.join(", "));
 
-    // Include regex
-    match include_regex {
-        Some(regex) => println!("{}: {}", "Include Regex".bold(), regex),
-        None => println!("{}: None", "Include Regex".bold()),
-    }
+    // Include words
+    println!("{}: {}", "Include Words".bold(), word_list(include_words));
 
-    // Exclude regex
-    match exclude_regex {
-        Some(regex) => println!("{}: {}", "Exclude Regex".bold(), regex),
-        None => println!("{}: None", "Exclude Regex".bold()),
-    }
+    // Exclude words
+    println!("{}: {}", "Exclude Words".bold(), word_list(exclude_words));
 
     println!("{}", "=".repeat(40).cyan());
     println!();
 }
+
+fn word_list(words: &[FilterWord]) -> String {
+    match words {
+        [] => "None".to_string(),
+        words => words.iter().map(|word| word.word.as_str()).collect::<Vec<_>>().join(", "),
+    }
+}
@@ -0,0 +1,58 @@
+use anyhow::{bail, Context, Result};
+use std::time::Duration;
+
+// Durations are added to the current time for deadlines, which must not overflow.
+const MAX_DURATION: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split_at = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split_at);
+    let amount: f64 = amount
+        .parse()
+        .with_context(|| format!("Invalid duration: {value}"))?;
+
+    let seconds = match unit {
+        "ms" => amount / 1000.0,
+        "" | "s" => amount,
+        "m" => amount * 60.0,
+        "h" => amount * 60.0 * 60.0,
+        _ => bail!("Invalid duration unit in {value}, expected one of ms, s, m, h"),
+    };
+
+    let duration = Duration::try_from_secs_f64(seconds).with_context(|| format!("Invalid duration: {value}"))?;
+    if duration > MAX_DURATION {
+        bail!("Duration too long: {value}, expected at most 100 years");
+    }
+
+    Ok(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_duration;
+    use std::time::Duration;
+
+    #[test]
+    fn parses_supported_units() {
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("2s").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_duration("1.5").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn rejects_unknown_units_and_missing_amounts() {
+        assert!(parse_duration("10d").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("").is_err());
+    }
+
+    #[test]
+    fn rejects_durations_that_are_too_long() {
+        assert!(parse_duration("99999999999999999999h").is_err());
+        assert!(parse_duration("1000000000000s").is_err());
+        assert!(parse_duration("876000h").is_ok());
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::{Context, Result};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+use tokio::io::{AsyncRead, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::mpsc;
+
+use crate::line_reader::LossyLines;
+
+const STOP_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+pub struct ExecLine {
+    pub stream: &'static str,
+    pub line: String,
+}
+
+pub fn spawn(command_line: &str) -> Result<(Child, mpsc::Receiver<ExecLine>)> {
+    // The child gets its own process group so a terminal Ctrl-C reaches it exactly once,
+    // through `forward_signal`, instead of both directly and forwarded.
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command_line)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("Failed to run command: {command_line}"))?;
+
+    let (sender, receiver) = mpsc::channel(1024);
+    let stdout = child.stdout.take().context("Failed to capture command stdout")?;
+    let stderr = child.stderr.take().context("Failed to capture command stderr")?;
+    tokio::spawn(read_lines(stdout, "stdout", sender.clone()));
+    tokio::spawn(read_lines(stderr, "stderr", sender));
+
+    Ok((child, receiver))
+}
+
+async fn read_lines(stream: impl AsyncRead + Unpin, name: &'static str, sender: mpsc::Sender<ExecLine>) {
+    let mut lines = LossyLines::new(BufReader::new(stream));
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("Error: Failed to read command {name}: {e:?}");
+                break;
+            }
+        };
+        if sender.send(ExecLine { stream: name, line }).await.is_err() {
+            break;
+        }
+    }
+}
+
+pub fn forward_signal(child: &Child, signal: libc::c_int) {
+    if let Some(pid) = child.id().and_then(|pid| libc::pid_t::try_from(pid).ok()) {
+        // SAFETY: kill has no memory-safety preconditions; a negative pid targets the child's process group.
+        unsafe {
+            libc::kill(-pid, signal);
+        }
+    }
+}
+
+// Asks the command's process group to end, and kills it should it still run after the grace period.
+pub async fn stop(child: &mut Child) -> Result<ExitStatus> {
+    forward_signal(child, libc::SIGTERM);
+    if let Ok(status) = tokio::time::timeout(STOP_GRACE_PERIOD, child.wait()).await {
+        return status.context("Failed to wait for command");
+    }
+
+    forward_signal(child, libc::SIGKILL);
+    child.wait().await.context("Failed to wait for command")
+}
+
+// Like a shell: the exit code, which is always 0-255 on Unix, or 128 plus the signal that ended the command.
+pub fn exit_code(status: ExitStatus) -> u8 {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => code as u8,
+        (None, Some(signal)) => u8::try_from(128 + signal).unwrap_or(u8::MAX),
+        (None, None) => u8::MAX,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{exit_code, forward_signal, spawn, stop};
+
+    #[tokio::test]
+    async fn reads_stdout_and_stderr_as_tagged_lines() {
+        let (mut child, mut receiver) = spawn("echo out; echo err >&2").unwrap();
+        let mut lines = Vec::new();
+        while let Some(line) = receiver.recv().await {
+            lines.push((line.stream, line.line));
+        }
+        lines.sort();
+
+        assert_eq!(lines, vec![("stderr", "err".to_string()), ("stdout", "out".to_string())]);
+        assert_eq!(exit_code(child.wait().await.unwrap()), 0);
+    }
+
+    #[tokio::test]
+    async fn keeps_reading_after_invalid_utf8() {
+        let (mut child, mut receiver) = spawn(r"printf 'bad \377\nafter\n'").unwrap();
+        let mut lines = Vec::new();
+        while let Some(line) = receiver.recv().await {
+            lines.push(line.line);
+        }
+
+        assert_eq!(lines, vec!["bad \u{fffd}".to_string(), "after".to_string()]);
+        assert_eq!(exit_code(child.wait().await.unwrap()), 0);
+    }
+
+    #[tokio::test]
+    async fn propagates_exit_codes_and_signals() {
+        let (mut child, _) = spawn("exit 3").unwrap();
+        assert_eq!(exit_code(child.wait().await.unwrap()), 3);
+
+        let (mut child, _) = spawn("sleep 10").unwrap();
+        forward_signal(&child, libc::SIGTERM);
+        assert_eq!(exit_code(child.wait().await.unwrap()), 128 + libc::SIGTERM as u8);
+
+        let (mut child, _) = spawn("exit 300").unwrap();
+        assert_eq!(exit_code(child.wait().await.unwrap()), 44);
+
+        let (mut child, _) = spawn("sleep 10").unwrap();
+        assert_eq!(exit_code(stop(&mut child).await.unwrap()), 128 + libc::SIGTERM as u8);
+    }
+}
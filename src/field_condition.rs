@@ -0,0 +1,142 @@
+use anyhow::{bail, Result};
+use regex::Regex;
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use crate::fields::Fields;
+
+const OPERATORS: &[(&str, Operator)] = &[
+    (">=", Operator::GreaterOrEqual),
+    ("<=", Operator::LessOrEqual),
+    ("!=", Operator::NotEqual),
+    ("==", Operator::Equal),
+    ("=~", Operator::Matches),
+    (">", Operator::Greater),
+    ("<", Operator::Less),
+    ("=", Operator::Equal),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Matches,
+}
+
+#[derive(Clone)]
+pub struct FieldCondition {
+    field: String,
+    operator: Operator,
+    value: String,
+    regex: Option<Regex>,
+}
+
+impl FieldCondition {
+    pub fn matches(&self, fields: &Fields) -> bool {
+        let Some(actual) = fields.get(&self.field) else {
+            return false;
+        };
+
+        if let Some(regex) = &self.regex {
+            return regex.is_match(actual);
+        }
+
+        let ordering = match (actual.parse::<f64>(), self.value.parse::<f64>()) {
+            (Ok(actual), Ok(expected)) => actual.partial_cmp(&expected),
+            _ => Some(actual.cmp(self.value.as_str())),
+        };
+
+        match self.operator {
+            Operator::Equal => ordering == Some(Ordering::Equal),
+            Operator::NotEqual => ordering != Some(Ordering::Equal),
+            Operator::Greater => ordering == Some(Ordering::Greater),
+            Operator::GreaterOrEqual => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            Operator::Less => ordering == Some(Ordering::Less),
+            Operator::LessOrEqual => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            Operator::Matches => unreachable!("regex conditions are handled above"),
+        }
+    }
+}
+
+impl FromStr for FieldCondition {
+    type Err = anyhow::Error;
+
+    fn from_str(expression: &str) -> Result<Self> {
+        let Some((index, symbol, operator)) = OPERATORS
+            .iter()
+            .filter_map(|&(symbol, operator)| expression.find(symbol).map(|index| (index, symbol, operator)))
+            .min_by_key(|&(index, symbol, _)| (index, std::cmp::Reverse(symbol.len())))
+        else {
+            bail!("Invalid condition: {expression}, expected FIELD<op>VALUE with one of = != > >= < <= =~");
+        };
+
+        let field = expression[..index].trim();
+        let value = expression[index + symbol.len()..].trim();
+        if field.is_empty() {
+            bail!("Missing field name in condition: {expression}");
+        }
+
+        Ok(Self {
+            field: field.to_string(),
+            operator,
+            value: value.to_string(),
+            regex: (operator == Operator::Matches).then(|| Regex::new(value)).transpose()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FieldCondition;
+    use crate::fields::Fields;
+
+    fn fields(entries: &[(&str, &str)]) -> Fields {
+        let mut fields = Fields::default();
+        for (name, value) in entries {
+            fields.insert(*name, *value);
+        }
+        fields
+    }
+
+    fn matches(expression: &str, fields: &Fields) -> bool {
+        expression.parse::<FieldCondition>().unwrap().matches(fields)
+    }
+
+    #[test]
+    fn compares_numeric_fields_as_numbers() {
+        let fields = fields(&[("latency", "1200"), ("status", "503")]);
+
+        assert!(matches("latency>250", &fields));
+        assert!(matches("latency >= 1200", &fields));
+        assert!(!matches("latency<1000", &fields));
+        assert!(matches("status>=500", &fields));
+        assert!(matches("status=503", &fields));
+        assert!(matches("status!=200", &fields));
+    }
+
+    #[test]
+    fn compares_text_fields_and_regexes() {
+        let fields = fields(&[("method", "POST"), ("path", "/api/users/42")]);
+
+        assert!(matches("method==POST", &fields));
+        assert!(!matches("method=GET", &fields));
+        assert!(matches(r"path=~^/api/users/\d+$", &fields));
+    }
+
+    #[test]
+    fn missing_fields_never_match() {
+        assert!(!matches("latency>250", &Fields::default()));
+        assert!(!matches("latency!=250", &Fields::default()));
+    }
+
+    #[test]
+    fn rejects_invalid_conditions() {
+        assert!("latency".parse::<FieldCondition>().is_err());
+        assert!(">250".parse::<FieldCondition>().is_err());
+        assert!("path=~(".parse::<FieldCondition>().is_err());
+    }
+}
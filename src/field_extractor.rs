@@ -0,0 +1,85 @@
+use anyhow::{bail, Result};
+use regex::Regex;
+
+use crate::fields::Fields;
+
+#[derive(Clone)]
+pub struct FieldExtractor {
+    regexes: Vec<Regex>,
+}
+
+impl FieldExtractor {
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        let regexes = patterns
+            .iter()
+            .map(|pattern| {
+                let regex = Regex::new(pattern)?;
+                if regex.capture_names().flatten().next().is_none() {
+                    bail!("Extract pattern has no named capture groups: {pattern}");
+                }
+                Ok(regex)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { regexes })
+    }
+
+    pub fn extract(&self, line: &str, fields: &mut Fields) {
+        for regex in &self.regexes {
+            let Some(captures) = regex.captures(line) else {
+                continue;
+            };
+
+            for name in regex.capture_names().flatten() {
+                if let Some(value) = captures.name(name) {
+                    fields.insert(name, value.as_str());
+                }
+            }
+        }
+    }
+
+    pub fn field_names(&self) -> impl Iterator<Item = &str> {
+        self.regexes.iter().flat_map(|regex| regex.capture_names().flatten())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FieldExtractor;
+    use crate::fields::Fields;
+
+    #[test]
+    fn extracts_named_groups_from_all_matching_patterns() {
+        let extractor = FieldExtractor::new(&[
+            r"(?P<status>\d{3}) (?P<latency>\d+)ms".to_string(),
+            r"user=(?P<user>\w+)".to_string(),
+        ])
+        .unwrap();
+        let mut fields = Fields::default();
+
+        extractor.extract("GET /api 503 412ms user=alice", &mut fields);
+
+        assert_eq!(fields.get("status"), Some("503"));
+        assert_eq!(fields.get("latency"), Some("412"));
+        assert_eq!(fields.get("user"), Some("alice"));
+        assert_eq!(extractor.field_names().collect::<Vec<_>>(), vec!["status", "latency", "user"]);
+    }
+
+    #[test]
+    fn skips_patterns_and_optional_groups_that_do_not_match() {
+        let extractor = FieldExtractor::new(&[r"id=(?P<id>\d+)(?: retry=(?P<retry>\d+))?".to_string()]).unwrap();
+        let mut fields = Fields::default();
+
+        extractor.extract("no identifiers here", &mut fields);
+        assert_eq!(fields, Fields::default());
+
+        extractor.extract("id=42", &mut fields);
+        assert_eq!(fields.get("id"), Some("42"));
+        assert_eq!(fields.get("retry"), None);
+    }
+
+    #[test]
+    fn rejects_patterns_without_named_groups() {
+        assert!(FieldExtractor::new(&[r"(\d+)ms".to_string()]).is_err());
+    }
+}
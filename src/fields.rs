@@ -0,0 +1,44 @@
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Fields {
+    entries: Vec<(String, String)>,
+}
+
+impl Fields {
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        let value = value.into();
+
+        match self.entries.iter_mut().find(|(existing, _)| *existing == name) {
+            Some((_, existing_value)) => *existing_value = value,
+            None => self.entries.push((name, value)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fields;
+
+    #[test]
+    fn keeps_insertion_order_and_overwrites_existing_names() {
+        let mut fields = Fields::default();
+        fields.insert("status", "200");
+        fields.insert("latency", "12");
+        fields.insert("status", "500");
+
+        assert_eq!(fields.get("status"), Some("500"));
+        assert_eq!(fields.get("missing"), None);
+        assert_eq!(fields.iter().collect::<Vec<_>>(), vec![("status", "500"), ("latency", "12")]);
+    }
+}
@@ -0,0 +1,109 @@
+use anyhow::{bail, Context, Result};
+use globset::{GlobBuilder, GlobMatcher};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::config_file::HighlightConfig;
+
+pub struct FileRule {
+    glob: String,
+    matcher: GlobMatcher,
+    pub include_words: Vec<String>,
+    pub exclude_words: Vec<String>,
+    pub highlight: Vec<HighlightConfig>,
+}
+
+impl FileRule {
+    // Relative globs match the end of a path, so "app.log" or "nginx/*.log" also match the absolute
+    // paths of followed files.
+    pub fn new(glob: &str) -> Result<Self> {
+        let pattern = if glob.starts_with('/') || glob.starts_with("**") {
+            glob.to_string()
+        } else {
+            format!("**/{glob}")
+        };
+        let matcher = GlobBuilder::new(&pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid file rule glob: {glob}"))?
+            .compile_matcher();
+
+        Ok(Self {
+            glob: glob.to_string(),
+            matcher,
+            include_words: Vec::new(),
+            exclude_words: Vec::new(),
+            highlight: Vec::new(),
+        })
+    }
+
+    pub fn glob(&self) -> &str {
+        &self.glob
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        self.matcher.is_match(path)
+    }
+}
+
+// A rule on the command line is a glob and the filter options for it, e.g. "app.log:-e heartbeat ping".
+impl FromStr for FileRule {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let Some((glob, options)) = spec.split_once(':') else {
+            bail!("Invalid file rule: {spec}, expected GLOB:OPTIONS");
+        };
+        let mut rule = Self::new(glob)?;
+
+        let mut exclude = None;
+        for option in options.split_whitespace() {
+            match option {
+                "-e" | "--exclude-words" => exclude = Some(true),
+                "-i" | "--include-words" => exclude = Some(false),
+                word => match exclude {
+                    Some(true) => rule.exclude_words.push(word.to_string()),
+                    Some(false) => rule.include_words.push(word.to_string()),
+                    None => bail!("Invalid file rule: {spec}, expected -e or -i before {word}"),
+                },
+            }
+        }
+        if rule.include_words.is_empty() && rule.exclude_words.is_empty() {
+            bail!("Invalid file rule: {spec}, expected -e or -i with words");
+        }
+
+        Ok(rule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileRule;
+    use std::path::Path;
+
+    #[test]
+    fn parses_rule_from_command_line() {
+        let rule: FileRule = "app.log:-e heartbeat ping -i ERROR".parse().unwrap();
+
+        assert_eq!(rule.exclude_words, vec!["heartbeat", "ping"]);
+        assert_eq!(rule.include_words, vec!["ERROR"]);
+        assert!("app.log".parse::<FileRule>().is_err());
+        assert!("app.log:heartbeat".parse::<FileRule>().is_err());
+        assert!("app.log:-e".parse::<FileRule>().is_err());
+    }
+
+    #[test]
+    fn relative_globs_match_the_end_of_the_path() {
+        let rule = FileRule::new("nginx/*.log").unwrap();
+        assert!(rule.matches(Path::new("/var/log/nginx/access.log")));
+        assert!(rule.matches(Path::new("nginx/error.log")));
+        assert!(!rule.matches(Path::new("/var/log/nginx/old/access.log")));
+
+        let rule = FileRule::new("app.log").unwrap();
+        assert!(rule.matches(Path::new("/srv/app.log")));
+        assert!(rule.matches(Path::new("app.log")));
+        assert!(!rule.matches(Path::new("/srv/myapp.log")));
+
+        assert!(!FileRule::new("/var/log/*.log").unwrap().matches(Path::new("/srv/var/log/app.log")));
+    }
+}
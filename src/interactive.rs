@@ -0,0 +1,241 @@
+use anyhow::Result;
+use std::io::{self, IsTerminal, Read, Write};
+use std::mem::MaybeUninit;
+use std::thread;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc;
+
+const HIGHLIGHT_KEY: u8 = b'h';
+const MARK_KEY: u8 = b'm';
+const ESCAPE: u8 = 0x1b;
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+const PROMPT: &str = "Highlight: ";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Highlight(String),
+    Mark,
+}
+
+pub struct Controls {
+    keys: mpsc::Receiver<u8>,
+    prompt: Option<Vec<u8>>,
+    _raw_mode: Option<RawMode>,
+}
+
+impl Controls {
+    // Reads single key presses from the terminal; returns None unless both stdin and stdout are terminals,
+    // so piping the output elsewhere (e.g. into tee) leaves the keyboard alone.
+    pub fn open() -> Result<Option<Self>> {
+        if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+            return Ok(None);
+        }
+
+        let raw_mode = RawMode::enable()?;
+        restore_on_termination(raw_mode.original)?;
+        let (sender, keys) = mpsc::channel(64);
+        thread::spawn(move || {
+            let mut stdin = io::stdin().lock();
+            let mut key = [0; 1];
+            while stdin.read_exact(&mut key).is_ok() {
+                if sender.blocking_send(key[0]).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Some(Self {
+            keys,
+            prompt: None,
+            _raw_mode: Some(raw_mode),
+        }))
+    }
+
+    pub fn is_prompting(&self) -> bool {
+        self.prompt.is_some()
+    }
+
+    pub async fn next_command(&mut self) -> Option<Command> {
+        loop {
+            let Some(key) = self.keys.recv().await else {
+                self.prompt = None;
+                return None;
+            };
+
+            let Some(term) = &mut self.prompt else {
+                match key {
+                    HIGHLIGHT_KEY => {
+                        self.prompt = Some(Vec::new());
+                        draw_prompt(b"");
+                    }
+                    MARK_KEY => return Some(Command::Mark),
+                    _ => {}
+                }
+                continue;
+            };
+
+            match key {
+                b'\r' | b'\n' => {
+                    let term = String::from_utf8_lossy(term).trim().to_string();
+                    self.prompt = None;
+                    clear_prompt();
+                    if !term.is_empty() {
+                        return Some(Command::Highlight(term));
+                    }
+                }
+                ESCAPE => {
+                    self.prompt = None;
+                    clear_prompt();
+                }
+                BACKSPACE | DELETE => {
+                    // Drop a whole UTF-8 character, not just its last byte.
+                    while let Some(byte) = term.pop() {
+                        if byte & 0xc0 != 0x80 {
+                            break;
+                        }
+                    }
+                    draw_prompt(term);
+                }
+                key if key >= 0x20 => {
+                    term.push(key);
+                    draw_prompt(term);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// Canonical input and echo are turned off so keys arrive one at a time, but signals stay on so
+// Ctrl-C still interrupts.
+struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    fn enable() -> Result<Self> {
+        let mut termios = MaybeUninit::<libc::termios>::uninit();
+        // SAFETY: tcgetattr fills the termios it is given when it returns 0, which is checked before reading it.
+        let original = unsafe {
+            if libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            termios.assume_init()
+        };
+
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        // SAFETY: raw is a valid termios copied from the terminal's current settings.
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Ok(Self { original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        restore_terminal(&self.original);
+    }
+}
+
+// SIGTERM and SIGHUP end the process without running destructors, so they are caught to restore the
+// terminal settings first, then exit with the status the signal would have given.
+fn restore_on_termination(original: libc::termios) -> Result<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        let signal_number = tokio::select! {
+            _ = terminate.recv() => libc::SIGTERM,
+            _ = hangup.recv() => libc::SIGHUP,
+        };
+        restore_terminal(&original);
+        std::process::exit(128 + signal_number);
+    });
+    Ok(())
+}
+
+fn restore_terminal(settings: &libc::termios) {
+    // SAFETY: settings were read from the terminal by tcgetattr in RawMode::enable.
+    unsafe {
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, settings);
+    }
+}
+
+pub fn mark_line() -> String {
+    format!("----- MARK {} -----", local_time())
+}
+
+fn local_time() -> String {
+    // SAFETY: time accepts a null pointer, and localtime_r only writes into the tm it is given,
+    // which is read only after it returned non-null.
+    let tm = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm = MaybeUninit::<libc::tm>::uninit();
+        if libc::localtime_r(&now, tm.as_mut_ptr()).is_null() {
+            return "--:--:--".to_string();
+        }
+        tm.assume_init()
+    };
+
+    format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec)
+}
+
+fn draw_prompt(term: &[u8]) {
+    let mut stderr = io::stderr();
+    let _ = write!(stderr, "\r\x1b[K{PROMPT}{}", String::from_utf8_lossy(term));
+    let _ = stderr.flush();
+}
+
+fn clear_prompt() {
+    let _ = write!(io::stderr(), "\r\x1b[K");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mark_line, Command, Controls};
+    use tokio::sync::mpsc;
+
+    fn controls_with_keys(keys: &[u8]) -> Controls {
+        let (sender, receiver) = mpsc::channel(64);
+        for &key in keys {
+            sender.try_send(key).unwrap();
+        }
+        Controls {
+            keys: receiver,
+            prompt: None,
+            _raw_mode: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn prompts_for_a_term_to_highlight() {
+        let mut controls = controls_with_keys(b"xhab\x7fc\r");
+        assert_eq!(controls.next_command().await, Some(Command::Highlight("ac".to_string())));
+        assert!(!controls.is_prompting());
+
+        let mut controls = controls_with_keys("h\u{e9}\u{e9}\x7f\n".as_bytes());
+        assert_eq!(controls.next_command().await, Some(Command::Highlight("\u{e9}".to_string())));
+    }
+
+    #[tokio::test]
+    async fn escape_and_empty_terms_cancel_the_prompt() {
+        let mut controls = controls_with_keys(b"hab\x1bh\rm");
+        assert_eq!(controls.next_command().await, Some(Command::Mark));
+
+        let mut controls = controls_with_keys(b"hab");
+        assert_eq!(controls.next_command().await, None);
+        assert!(!controls.is_prompting());
+    }
+
+    #[test]
+    fn mark_line_shows_the_local_time() {
+        let mark = mark_line();
+        assert!(mark.starts_with("----- MARK ") && mark.ends_with(" -----"));
+        assert_eq!(mark.len(), "----- MARK 14:03:22 -----".len());
+    }
+}
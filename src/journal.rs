@@ -0,0 +1,189 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use tokio::process::Command;
+
+use crate::fields::Fields;
+
+pub struct JournalOptions {
+    pub units: Vec<String>,
+    pub follow: bool,
+    pub cursor_file: Option<PathBuf>,
+}
+
+impl JournalOptions {
+    pub fn command(&self, after_cursor: Option<&str>) -> Command {
+        let mut command = Command::new("journalctl");
+        command.args(["--output", "json", "--no-pager"]);
+        if self.follow {
+            command.arg("--follow");
+        }
+        for unit in &self.units {
+            command.args(["--unit", unit]);
+        }
+        if let Some(cursor) = after_cursor {
+            command.args(["--after-cursor", cursor]);
+        }
+        command
+    }
+
+    pub fn load_cursor(&self) -> Result<Option<String>> {
+        let Some(cursor_file) = &self.cursor_file else {
+            return Ok(None);
+        };
+
+        match fs::read_to_string(cursor_file) {
+            Ok(cursor) => Ok(Some(cursor.trim().to_string()).filter(|cursor| !cursor.is_empty())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read cursor file: {}", cursor_file.display())),
+        }
+    }
+
+    pub fn save_cursor(&self, cursor: &str) -> Result<()> {
+        let Some(cursor_file) = &self.cursor_file else {
+            return Ok(());
+        };
+
+        fs::write(cursor_file, format!("{cursor}\n"))
+            .with_context(|| format!("Failed to write cursor file: {}", cursor_file.display()))
+    }
+}
+
+pub struct JournalEntry {
+    pub line: String,
+    pub fields: Fields,
+    pub cursor: Option<String>,
+}
+
+impl JournalEntry {
+    pub fn parse(json: &str) -> Result<Self> {
+        let entry: Value = serde_json::from_str(json).context("Failed to parse journalctl output")?;
+        let message = field_text(&entry, "MESSAGE").unwrap_or_default();
+        let unit = field_text(&entry, "_SYSTEMD_UNIT");
+        let pid = field_text(&entry, "_PID");
+        let identifier = field_text(&entry, "SYSLOG_IDENTIFIER")
+            .or_else(|| unit.clone())
+            .unwrap_or_else(|| "journal".to_string());
+
+        let line = match &pid {
+            Some(pid) => format!("{identifier}[{pid}]: {message}"),
+            None => format!("{identifier}: {message}"),
+        };
+
+        let mut fields = Fields::default();
+        for (name, value) in [
+            ("unit", unit),
+            ("priority", field_text(&entry, "PRIORITY")),
+            ("pid", pid),
+            ("identifier", Some(identifier)),
+            ("message", Some(message)),
+        ] {
+            if let Some(value) = value {
+                fields.insert(name, value);
+            }
+        }
+
+        Ok(Self {
+            line,
+            fields,
+            cursor: field_text(&entry, "__CURSOR"),
+        })
+    }
+}
+
+// journalctl encodes non-UTF-8 or binary field values as arrays of bytes.
+fn field_text(entry: &Value, name: &str) -> Option<String> {
+    match entry.get(name)? {
+        Value::String(text) => Some(text.clone()),
+        Value::Array(bytes) => {
+            let bytes: Vec<u8> = bytes
+                .iter()
+                .filter_map(Value::as_u64)
+                .filter_map(|byte| u8::try_from(byte).ok())
+                .collect();
+            Some(String::from_utf8_lossy(&bytes).into_owned())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JournalEntry, JournalOptions};
+    use std::path::PathBuf;
+
+    #[test]
+    fn parses_journal_json_entries() {
+        let entry = JournalEntry::parse(
+            r#"{"__CURSOR":"s=abc;i=1","MESSAGE":"Started nginx","PRIORITY":"6","_PID":"1","SYSLOG_IDENTIFIER":"systemd","_SYSTEMD_UNIT":"init.scope"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(entry.line, "systemd[1]: Started nginx");
+        assert_eq!(entry.cursor.as_deref(), Some("s=abc;i=1"));
+        assert_eq!(entry.fields.get("unit"), Some("init.scope"));
+        assert_eq!(entry.fields.get("priority"), Some("6"));
+        assert_eq!(entry.fields.get("pid"), Some("1"));
+        assert_eq!(entry.fields.get("message"), Some("Started nginx"));
+    }
+
+    #[test]
+    fn decodes_byte_array_messages_and_missing_fields() {
+        let entry = JournalEntry::parse(r#"{"MESSAGE":[104,105,255],"_SYSTEMD_UNIT":"app.service"}"#).unwrap();
+
+        assert_eq!(entry.line, "app.service: hi\u{fffd}");
+        assert_eq!(entry.fields.get("pid"), None);
+        assert!(entry.cursor.is_none());
+        assert!(JournalEntry::parse("not json").is_err());
+    }
+
+    #[test]
+    fn builds_journalctl_command() {
+        let options = JournalOptions {
+            units: vec!["nginx.service".to_string(), "sshd.service".to_string()],
+            follow: true,
+            cursor_file: None,
+        };
+        let command = options.command(Some("s=abc"));
+        let args: Vec<_> = command.as_std().get_args().map(|arg| arg.to_str().unwrap()).collect();
+
+        assert_eq!(
+            args,
+            vec![
+                "--output",
+                "json",
+                "--no-pager",
+                "--follow",
+                "--unit",
+                "nginx.service",
+                "--unit",
+                "sshd.service",
+                "--after-cursor",
+                "s=abc"
+            ]
+        );
+    }
+
+    #[test]
+    fn saves_and_loads_cursor_file() {
+        let cursor_file = std::env::temp_dir().join(format!("log-viewer-cursor-{}", std::process::id()));
+        let options = JournalOptions {
+            units: Vec::new(),
+            follow: false,
+            cursor_file: Some(cursor_file.clone()),
+        };
+
+        assert_eq!(options.load_cursor().unwrap(), None);
+        options.save_cursor("s=abc;i=2").unwrap();
+        assert_eq!(options.load_cursor().unwrap().as_deref(), Some("s=abc;i=2"));
+        std::fs::remove_file(cursor_file).unwrap();
+
+        let without_file = JournalOptions {
+            cursor_file: None::<PathBuf>,
+            ..options
+        };
+        assert!(without_file.save_cursor("ignored").is_ok());
+    }
+}
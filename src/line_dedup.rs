@@ -0,0 +1,141 @@
+use regex::Regex;
+use std::borrow::Cow;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+static TIMESTAMP_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(concat!(
+        r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?",
+        r"|\b[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}\b",
+        r"|\b\d{2}:\d{2}:\d{2}(?:[.,]\d+)?\b",
+    ))
+    .expect("timestamp regex must compile")
+});
+
+pub enum DedupOutcome {
+    Emit { collapsed: Option<usize> },
+    Suppress,
+}
+
+pub struct LineDeduplicator {
+    window: Duration,
+    last_key: Option<String>,
+    repeats: usize,
+    first_repeat_at: Option<Instant>,
+}
+
+impl LineDeduplicator {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last_key: None,
+            repeats: 0,
+            first_repeat_at: None,
+        }
+    }
+
+    pub fn observe(&mut self, line: &str, now: Instant) -> DedupOutcome {
+        let key = mask_timestamps(line.trim_end_matches(['\r', '\n']));
+
+        if self.last_key.as_deref() == Some(key.as_ref()) {
+            self.repeats += 1;
+            self.first_repeat_at.get_or_insert(now);
+            return DedupOutcome::Suppress;
+        }
+
+        let collapsed = self.flush();
+        self.last_key = Some(key.into_owned());
+        DedupOutcome::Emit { collapsed }
+    }
+
+    pub fn flush(&mut self) -> Option<usize> {
+        self.first_repeat_at = None;
+        match std::mem::take(&mut self.repeats) {
+            0 => None,
+            repeats => Some(repeats),
+        }
+    }
+
+    pub fn flush_expired(&mut self, now: Instant) -> Option<usize> {
+        match self.deadline() {
+            Some(deadline) if deadline <= now => self.flush(),
+            _ => None,
+        }
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.first_repeat_at.map(|first_repeat_at| first_repeat_at + self.window)
+    }
+}
+
+pub fn repeat_notice(repeats: usize) -> String {
+    format!("(repeated {repeats} times)")
+}
+
+fn mask_timestamps(line: &str) -> Cow<'_, str> {
+    TIMESTAMP_REGEX.replace_all(line, "<timestamp>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mask_timestamps, repeat_notice, DedupOutcome, LineDeduplicator};
+    use std::time::{Duration, Instant};
+
+    fn collapsed(outcome: DedupOutcome) -> Option<Option<usize>> {
+        match outcome {
+            DedupOutcome::Emit { collapsed } => Some(collapsed),
+            DedupOutcome::Suppress => None,
+        }
+    }
+
+    #[test]
+    fn collapses_consecutive_identical_lines() {
+        let mut dedup = LineDeduplicator::new(Duration::from_secs(2));
+        let now = Instant::now();
+
+        assert_eq!(collapsed(dedup.observe("boom\n", now)), Some(None));
+        assert_eq!(collapsed(dedup.observe("boom\n", now)), None);
+        assert_eq!(collapsed(dedup.observe("boom\n", now)), None);
+        assert_eq!(collapsed(dedup.observe("recovered\n", now)), Some(Some(2)));
+        assert_eq!(collapsed(dedup.observe("boom\n", now)), Some(None));
+    }
+
+    #[test]
+    fn treats_lines_differing_only_in_timestamps_as_repeats() {
+        let mut dedup = LineDeduplicator::new(Duration::from_secs(2));
+        let now = Instant::now();
+
+        assert_eq!(collapsed(dedup.observe("2024-01-01T00:00:00Z crash", now)), Some(None));
+        assert_eq!(collapsed(dedup.observe("2024-01-01T00:00:01.250Z crash", now)), None);
+        assert_eq!(dedup.flush(), Some(1));
+        assert_eq!(dedup.flush(), None);
+    }
+
+    #[test]
+    fn flushes_pending_repeats_once_window_expires() {
+        let mut dedup = LineDeduplicator::new(Duration::from_secs(2));
+        let start = Instant::now();
+
+        dedup.observe("boom", start);
+        dedup.observe("boom", start + Duration::from_secs(1));
+        dedup.observe("boom", start + Duration::from_millis(1500));
+
+        assert_eq!(dedup.deadline(), Some(start + Duration::from_secs(3)));
+        assert_eq!(dedup.flush_expired(start + Duration::from_secs(2)), None);
+        assert_eq!(dedup.flush_expired(start + Duration::from_secs(3)), Some(2));
+        assert_eq!(dedup.deadline(), None);
+        assert_eq!(collapsed(dedup.observe("boom", start + Duration::from_secs(4))), None);
+    }
+
+    #[test]
+    fn masks_common_timestamp_formats() {
+        assert_eq!(mask_timestamps("Jan  5 10:00:01 host app"), "<timestamp> host app");
+        assert_eq!(mask_timestamps("[12:30:45.123] ready"), "[<timestamp>] ready");
+        assert_eq!(mask_timestamps("no time here"), "no time here");
+    }
+
+    #[test]
+    fn formats_repeat_notice() {
+        assert_eq!(repeat_notice(3412), "(repeated 3412 times)");
+    }
+}
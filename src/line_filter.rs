@@ -1,57 +1,180 @@
 use anyhow::Result;
-use regex::Regex;
+use std::fmt;
 
-use crate::constants::PRESET_EXCLUDE_WORDS;
-use crate::word_pattern::build_word_pattern;
+use crate::matching::WordSet;
 
+// Exclude words come first, so they win over include words found at the same position.
+const EXCLUDE: usize = 0;
+const INCLUDE: usize = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WordOrigin {
+    CommandLine,
+    Profile(String),
+    FileRule(String),
+}
+
+impl fmt::Display for WordOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WordOrigin::CommandLine => write!(f, "command line"),
+            WordOrigin::Profile(name) => write!(f, "preset of profile {name}"),
+            WordOrigin::FileRule(glob) => write!(f, "file rule {glob}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterWord {
+    pub word: String,
+    pub origin: WordOrigin,
+}
+
+impl FilterWord {
+    pub fn new(word: &str, origin: WordOrigin) -> Self {
+        Self {
+            word: word.to_string(),
+            origin,
+        }
+    }
+}
+
+impl fmt::Display for FilterWord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} from {}", self.word, self.origin)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FilterDecision<'a> {
+    Empty,
+    Excluded(&'a FilterWord),
+    NotIncluded,
+    Allowed(Option<&'a FilterWord>),
+}
+
+impl FilterDecision<'_> {
+    pub fn allows(&self) -> bool {
+        matches!(self, FilterDecision::Allowed(_))
+    }
+
+    pub fn reason(&self) -> Option<String> {
+        match self {
+            FilterDecision::Empty | FilterDecision::Allowed(None) => None,
+            FilterDecision::Excluded(word) => Some(format!("excluded by {word}")),
+            FilterDecision::NotIncluded => Some("excluded: no include word matched".to_string()),
+            FilterDecision::Allowed(Some(word)) => Some(format!("included by {word}")),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct LineFilter {
-    include_regex: Option<Regex>,
-    exclude_regex: Option<Regex>,
+    include_words: Vec<FilterWord>,
+    exclude_words: Vec<FilterWord>,
+    words: WordSet,
 }
 
 impl LineFilter {
     pub fn new(
         include_words: Option<Vec<String>>,
         exclude_words: Option<Vec<String>>,
-        disable_preset_excludes: bool,
+        preset_exclude_words: Vec<FilterWord>,
     ) -> Result<Self> {
-        let mut exclude_word_list = exclude_words.unwrap_or_default();
-        if !disable_preset_excludes {
-            exclude_word_list.extend(PRESET_EXCLUDE_WORDS.iter().map(|&s| s.to_string()));
-        }
+        let from_command_line = |words: Option<Vec<String>>| -> Vec<FilterWord> {
+            words
+                .unwrap_or_default()
+                .iter()
+                .map(|word| FilterWord::new(word, WordOrigin::CommandLine))
+                .collect()
+        };
+        let mut exclude_word_list = from_command_line(exclude_words);
+        exclude_word_list.extend(preset_exclude_words);
+
+        Self::from_words(from_command_line(include_words), exclude_word_list)
+    }
+
+    // Adds more words to both lists, as profiles and file rules do.
+    pub fn with_words(&self, include_words: &[FilterWord], exclude_words: &[FilterWord]) -> Result<Self> {
+        Self::from_words(
+            [self.include_words.as_slice(), include_words].concat(),
+            [self.exclude_words.as_slice(), exclude_words].concat(),
+        )
+    }
+
+    fn from_words(include_words: Vec<FilterWord>, exclude_words: Vec<FilterWord>) -> Result<Self> {
+        let texts = |words: &[FilterWord]| words.iter().map(|word| word.word.clone()).collect::<Vec<_>>();
+        let words = WordSet::new(&[&texts(&exclude_words), &texts(&include_words)])?;
 
         Ok(Self {
-            include_regex: build_word_pattern(&include_words.unwrap_or_default())?,
-            exclude_regex: build_word_pattern(&exclude_word_list)?,
+            include_words,
+            exclude_words,
+            words,
         })
     }
 
+    // Include and exclude words are searched in the same scan, which ends at the first exclude word.
+    // With words of one kind only, any match decides.
     pub fn allows(&self, line: &str) -> bool {
         if line.is_empty() {
             return false;
         }
 
-        let passes_exclusion_filter = self.exclude_regex.as_ref().is_none_or(|re| !re.is_match(line));
-        let passes_inclusion_filter = self.include_regex.as_ref().is_none_or(|re| re.is_match(line));
+        match (self.words.has_words(INCLUDE), self.words.has_words(EXCLUDE)) {
+            (false, _) => !self.words.is_match(line),
+            (true, false) => self.words.is_match(line),
+            (true, true) => {
+                let mut included = false;
+                for found in self.words.find_iter(line) {
+                    match found.list {
+                        EXCLUDE => return false,
+                        _ => included = true,
+                    }
+                }
+                included
+            }
+        }
+    }
+
+    // Like allows, but also finds the word that decided: the leftmost one, or the first listed of those
+    // found at the same position.
+    pub fn decide(&self, line: &str) -> FilterDecision<'_> {
+        if line.is_empty() {
+            return FilterDecision::Empty;
+        }
 
-        passes_exclusion_filter && passes_inclusion_filter
+        let mut included = None;
+        for found in self.words.find_iter(line) {
+            match found.list {
+                EXCLUDE => return FilterDecision::Excluded(&self.exclude_words[found.index]),
+                _ => {
+                    included.get_or_insert(&self.include_words[found.index]);
+                }
+            }
+        }
+        match included {
+            Some(included) => FilterDecision::Allowed(Some(included)),
+            None if self.words.has_words(INCLUDE) => FilterDecision::NotIncluded,
+            None => FilterDecision::Allowed(None),
+        }
     }
 
-    pub fn include_regex(&self) -> &Option<Regex> {
-        &self.include_regex
+    pub fn include_words(&self) -> &[FilterWord] {
+        &self.include_words
     }
 
-    pub fn exclude_regex(&self) -> &Option<Regex> {
-        &self.exclude_regex
+    pub fn exclude_words(&self) -> &[FilterWord] {
+        &self.exclude_words
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::LineFilter;
+    use super::{FilterDecision, FilterWord, LineFilter, WordOrigin};
+    use crate::constants::{preset_words, PRESET_EXCLUDE_WORDS};
 
     #[test]
-    fn builds_include_regex_from_words() {
+    fn allows_lines_with_any_include_word() {
         let filter = LineFilter::new(
             Some(vec![
                 "error".to_string(),
@@ -61,17 +184,17 @@ mod tests {
                 "exception".to_string(),
             ]),
             None,
-            true,
+            Vec::new(),
         )
         .unwrap();
-        let regex = filter.include_regex().as_ref().unwrap();
-        assert!(regex.is_match("this is a critical error"));
-        assert!(regex.is_match("a fatal exception occurred"));
-        assert!(!regex.is_match("this is just info"));
+        assert_eq!(filter.include_words().len(), 5);
+        assert!(filter.allows("this is a critical error"));
+        assert!(filter.allows("a fatal exception occurred"));
+        assert!(!filter.allows("this is just info"));
     }
 
     #[test]
-    fn builds_exclude_regex_with_user_and_preset_words() {
+    fn excludes_lines_with_user_and_preset_words() {
         let filter = LineFilter::new(
             None,
             Some(vec![
@@ -81,15 +204,15 @@ mod tests {
                 "verbose".to_string(),
                 "temp".to_string(),
             ]),
-            false,
+            preset_words(),
         )
         .unwrap();
-        let regex = filter.exclude_regex().as_ref().unwrap();
-        assert!(regex.is_match("this is a debug message"));
-        assert!(regex.is_match("filter out this spam"));
-        assert!(regex.is_match("verbose logging enabled"));
-        assert!(regex.is_match("this is an aaa message"));
-        assert!(!regex.is_match("this is a regular message"));
+        assert_eq!(filter.exclude_words().len(), 5 + PRESET_EXCLUDE_WORDS.len());
+        assert!(!filter.allows("this is a debug message"));
+        assert!(!filter.allows("filter out this spam"));
+        assert!(!filter.allows("verbose logging enabled"));
+        assert!(!filter.allows("this is an aaa message"));
+        assert!(filter.allows("this is a regular message"));
     }
 
     #[test]
@@ -97,7 +220,7 @@ mod tests {
         let filter = LineFilter::new(
             Some(vec!["success".to_string(), "approved".to_string()]),
             Some(vec!["temp".to_string(), "interim".to_string()]),
-            false,
+            preset_words(),
         )
         .unwrap();
 
@@ -105,14 +228,70 @@ mod tests {
         assert!(!filter.allows("operation failed"));
         assert!(!filter.allows("interim success report"));
 
-        let include_only_filter = LineFilter::new(Some(vec!["approved".to_string()]), None, true).unwrap();
+        let include_only_filter = LineFilter::new(Some(vec!["approved".to_string()]), None, Vec::new()).unwrap();
         assert!(include_only_filter.allows("request approved"));
 
-        let exclude_only_filter = LineFilter::new(None, Some(vec!["temp".to_string()]), true).unwrap();
+        let exclude_only_filter = LineFilter::new(None, Some(vec!["temp".to_string()]), Vec::new()).unwrap();
         assert!(!exclude_only_filter.allows("this is a temp file"));
 
-        let no_rules_filter = LineFilter::new(None, None, true).unwrap();
+        let no_rules_filter = LineFilter::new(None, None, Vec::new()).unwrap();
         assert!(no_rules_filter.allows("any other message"));
         assert!(!no_rules_filter.allows(""));
     }
+
+    #[test]
+    fn exclude_words_inside_include_words_still_exclude() {
+        let filter = LineFilter::new(
+            Some(vec!["error".to_string()]),
+            Some(vec!["rror 5".to_string(), String::new()]),
+            Vec::new(),
+        )
+        .unwrap();
+
+        assert!(!filter.allows("an error 503"));
+        assert!(filter.allows("an error 404"));
+        assert!(!LineFilter::new(Some(vec!["error".to_string()]), None, Vec::new()).unwrap().allows("warn"));
+    }
+
+    #[test]
+    fn with_words_extends_both_lists() {
+        let filter = LineFilter::new(Some(vec!["GET".to_string()]), None, preset_words()).unwrap();
+        let file_rule = WordOrigin::FileRule("app.log".to_string());
+        let file_filter = filter
+            .with_words(
+                &[FilterWord::new("POST", file_rule.clone())],
+                &[FilterWord::new("heartbeat", file_rule)],
+            )
+            .unwrap();
+
+        assert!(file_filter.allows("POST /login"));
+        assert!(!file_filter.allows("GET /heartbeat"));
+        assert!(!file_filter.allows("GET /aaa"));
+        assert!(filter.allows("GET /heartbeat"));
+        assert!(!filter.allows("POST /login"));
+    }
+
+    #[test]
+    fn decide_reports_the_word_and_its_origin() {
+        let filter = LineFilter::new(
+            Some(vec!["GET".to_string(), "POST".to_string()]),
+            Some(vec!["healthz".to_string()]),
+            preset_words(),
+        )
+        .unwrap();
+
+        let decision = filter.decide("GET /aaa");
+        assert!(!decision.allows());
+        assert_eq!(decision.reason().unwrap(), r#"excluded by "aaa" from preset of profile default"#);
+        assert_eq!(
+            filter.decide("GET /healthz").reason().unwrap(),
+            r#"excluded by "healthz" from command line"#
+        );
+        assert_eq!(filter.decide("DELETE /x"), FilterDecision::NotIncluded);
+        assert_eq!(filter.decide("POST /x").reason().unwrap(), r#"included by "POST" from command line"#);
+        assert_eq!(filter.decide(""), FilterDecision::Empty);
+
+        let no_rules_filter = LineFilter::new(None, None, Vec::new()).unwrap();
+        assert_eq!(no_rules_filter.decide("anything"), FilterDecision::Allowed(None));
+    }
 }
@@ -1,77 +1,292 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use colored::{self, ColoredString, Colorize};
 use regex::Regex;
 use std::borrow::Cow;
+use std::ops::Range;
+use std::str::FromStr;
 
 use crate::constants::{CRITICAL_WORDS, INFO_WORDS, WARN_WORDS};
+use crate::matching::PatternSet;
+use crate::token_highlighter::{token_rules, TokenClass};
 use crate::word_pattern::build_word_pattern_from_strs;
 
-#[allow(dead_code)]
-pub enum Color {
-    Red,
-    BrightRed,
-    Green,
-    Yellow,
-    Blue,
-    Cyan,
-}
+pub use colored::Color;
 
-#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Style {
     Bold,
+    Dimmed,
     Italic,
     Underline,
-    Normal,
+    Reversed,
+    Blink,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextStyle {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub styles: Vec<Style>,
+}
+
+impl TextStyle {
+    pub fn new(foreground: Color, styles: &[Style]) -> Self {
+        Self {
+            foreground: Some(foreground),
+            background: None,
+            styles: styles.to_vec(),
+        }
+    }
 }
 
+// Parses specs like "bold underline #ff8800 on black": styles and a foreground color, optionally
+// followed by "on" and a background color. Colors are names ("red", "bright-red"), 256-color
+// indexes ("208") or hex truecolor ("#ff8800", "#f80").
+impl FromStr for TextStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let mut text_style = TextStyle::default();
+        let mut words = spec.split_whitespace();
+
+        while let Some(word) = words.next() {
+            let word = word.to_ascii_lowercase();
+            if word == "on" {
+                let background = words.next().with_context(|| format!("Missing background color in style: {spec}"))?;
+                if text_style.background.replace(parse_color(background)?).is_some() {
+                    bail!("More than one background color in style: {spec}");
+                }
+                continue;
+            }
+
+            match parse_style(&word) {
+                Some(style) => text_style.styles.push(style),
+                None => {
+                    let color = parse_color(&word).with_context(|| format!("Invalid style: {spec}"))?;
+                    if text_style.foreground.replace(color).is_some() {
+                        bail!("More than one foreground color in style: {spec}");
+                    }
+                }
+            }
+        }
+
+        if text_style == TextStyle::default() {
+            bail!("Empty style: {spec:?}");
+        }
+
+        Ok(text_style)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HighlightScope {
+    Match,
+    Group(usize),
+    Line,
+}
+
+// Medium-bright colors that stay readable on both dark and light backgrounds.
+const HASH_PALETTE: [u8; 12] = [25, 28, 31, 37, 64, 91, 96, 127, 130, 133, 166, 172];
+const TERM_COLORS: [(Color, Color); 6] = [
+    (Color::Black, Color::Yellow),
+    (Color::Black, Color::Cyan),
+    (Color::Black, Color::Magenta),
+    (Color::Black, Color::Green),
+    (Color::White, Color::Blue),
+    (Color::White, Color::Red),
+];
+
+#[derive(Clone)]
 pub struct HighlightRule {
     pub regex: Regex,
-    pub color: Color,
-    pub style: Style,
+    pub style: TextStyle,
+    pub scope: HighlightScope,
+    pub color_by_hash: bool,
+}
+
+impl HighlightRule {
+    // The color "hash" picks a foreground per matched text, so the same ID always gets the same color.
+    pub fn new(pattern: &str, style: &str, scope: &str) -> Result<Self> {
+        let regex = Regex::new(pattern).with_context(|| format!("Invalid highlight pattern: {pattern}"))?;
+        let scope = parse_scope(scope, &regex)?;
+
+        let (hash_words, style_words): (Vec<_>, Vec<_>) =
+            style.split_whitespace().partition(|word| word.eq_ignore_ascii_case("hash"));
+        let color_by_hash = !hash_words.is_empty();
+        let style = match style_words.join(" ").parse::<TextStyle>() {
+            Ok(text_style) if color_by_hash && text_style.foreground.is_some() => {
+                bail!("A hash colored style cannot also have a foreground color: {style}")
+            }
+            Ok(text_style) => text_style,
+            Err(_) if color_by_hash && style_words.is_empty() => TextStyle::default(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            regex,
+            style,
+            scope,
+            color_by_hash,
+        })
+    }
+
+    fn style_for(&self, matched: &str) -> Cow<'_, TextStyle> {
+        if !self.color_by_hash {
+            return Cow::Borrowed(&self.style);
+        }
+
+        let index = fnv1a_hash(matched.as_bytes()) % HASH_PALETTE.len() as u64;
+        Cow::Owned(TextStyle {
+            foreground: Some(Color::AnsiColor(HASH_PALETTE[index as usize])),
+            ..self.style.clone()
+        })
+    }
+
+    fn match_ranges<'a>(&'a self, line: &'a str) -> Box<dyn Iterator<Item = Range<usize>> + 'a> {
+        match self.scope {
+            HighlightScope::Group(index) => Box::new(
+                self.regex
+                    .captures_iter(line)
+                    .filter_map(move |caps| caps.get(index))
+                    .map(|group| group.range()),
+            ),
+            _ => Box::new(self.regex.find_iter(line).map(|found| found.range())),
+        }
+    }
 }
 
+pub struct HighlightSpan {
+    pub range: Range<usize>,
+    pub style: TextStyle,
+}
+
+#[derive(Clone)]
 pub struct LineHighlighter {
     rules: Vec<HighlightRule>,
+    // Kept in sync with rules, so a line is only searched for spans of the rules that match it.
+    rule_set: PatternSet,
+    added_terms: usize,
 }
 
 impl LineHighlighter {
     pub fn new() -> Result<Self> {
+        let rules = vec![
+            HighlightRule {
+                regex: build_word_pattern_from_strs(CRITICAL_WORDS)?.unwrap(),
+                style: TextStyle::new(Color::BrightRed, &[Style::Bold]),
+                scope: HighlightScope::Match,
+                color_by_hash: false,
+            },
+            HighlightRule {
+                regex: build_word_pattern_from_strs(WARN_WORDS)?.unwrap(),
+                style: TextStyle::new(Color::Yellow, &[Style::Underline]),
+                scope: HighlightScope::Match,
+                color_by_hash: false,
+            },
+            HighlightRule {
+                regex: build_word_pattern_from_strs(INFO_WORDS)?.unwrap(),
+                style: TextStyle::new(Color::Cyan, &[]),
+                scope: HighlightScope::Match,
+                color_by_hash: false,
+            },
+        ];
+
         Ok(Self {
-            rules: vec![
-                HighlightRule {
-                    regex: build_word_pattern_from_strs(CRITICAL_WORDS)?.unwrap(),
-                    color: Color::BrightRed,
-                    style: Style::Bold,
-                },
-                HighlightRule {
-                    regex: build_word_pattern_from_strs(WARN_WORDS)?.unwrap(),
-                    color: Color::Yellow,
-                    style: Style::Underline,
-                },
-                HighlightRule {
-                    regex: build_word_pattern_from_strs(INFO_WORDS)?.unwrap(),
-                    color: Color::Cyan,
-                    style: Style::Normal,
-                },
-            ],
+            rule_set: PatternSet::new(rules.iter().map(|rule| &rule.regex)),
+            rules,
+            added_terms: 0,
         })
     }
 
+    // User rules go before the built-in ones so they win where both match.
+    pub fn with_rules(mut self, rules: Vec<HighlightRule>) -> Self {
+        self.rules.splice(0..0, rules);
+        self.update_rule_set();
+        self
+    }
+
+    // Token rules go after all others, so they only color what no other rule matched.
+    pub fn with_tokens(mut self, classes: &[TokenClass]) -> Self {
+        self.rules.extend(token_rules(classes));
+        self.update_rule_set();
+        self
+    }
+
+    // Terms added while watching take the next color of their own palette and win over every other rule.
+    pub fn add_term(&mut self, term: &str) {
+        let (foreground, background) = TERM_COLORS[self.added_terms % TERM_COLORS.len()];
+        self.added_terms += 1;
+        self.rules.insert(
+            0,
+            HighlightRule {
+                regex: Regex::new(&regex::escape(term)).expect("escaped terms are valid patterns"),
+                style: TextStyle {
+                    foreground: Some(foreground),
+                    background: Some(background),
+                    styles: Vec::new(),
+                },
+                scope: HighlightScope::Match,
+                color_by_hash: false,
+            },
+        );
+        self.update_rule_set();
+    }
+
+    #[cfg(test)]
     pub fn apply<'a>(&self, line: &'a str) -> Cow<'a, str> {
-        let mut line: Cow<'a, str> = Cow::Borrowed(line);
-
-        for rule in &self.rules {
-            line = Cow::Owned(
-                rule.regex
-                    .replace_all(&line, |caps: &regex::Captures| {
-                        let matched_word = &caps[0];
-                        apply_style(matched_word, &rule.color, &rule.style).to_string()
-                    })
-                    .into_owned(),
-            );
+        self.apply_with_spans(line, &[])
+    }
+
+    // Every rule matches against the original line, never against another rule's escape codes. Where
+    // matches overlap, the given spans win, then earlier rules. Match and group highlights are rendered
+    // inside a whole-line style; only the first matching line rule is used.
+    pub fn apply_with_spans<'a>(&self, text: &'a str, spans: &[HighlightSpan]) -> Cow<'a, str> {
+        let (line, line_ending) = split_line_ending(text);
+        let mut resolved_spans = Vec::new();
+        for span in spans {
+            let range = span.range.start..span.range.end.min(line.len());
+            insert_span(&mut resolved_spans, range, Cow::Borrowed(&span.style));
+        }
+        let matching_rules: Vec<_> = self.rule_set.matching(line).into_iter().map(|index| &self.rules[index]).collect();
+        if spans.is_empty() && matching_rules.is_empty() {
+            return Cow::Borrowed(text);
+        }
+
+        for rule in matching_rules.iter().filter(|rule| rule.scope != HighlightScope::Line) {
+            for range in rule.match_ranges(line) {
+                let style = rule.style_for(&line[range.clone()]);
+                insert_span(&mut resolved_spans, range, style);
+            }
         }
 
-        line
+        let line_codes = matching_rules
+            .iter()
+            .filter(|rule| rule.scope == HighlightScope::Line)
+            .find_map(|rule| rule.regex.find(line).map(|found| rule.style_for(found.as_str())))
+            .and_then(|style| style_codes(&style));
+        if resolved_spans.is_empty() && line_codes.is_none() {
+            return Cow::Borrowed(text);
+        }
+
+        let (line_prefix, line_reset) = line_codes.unwrap_or_default();
+        let mut highlighted = String::with_capacity(line.len() + resolved_spans.len() * 16);
+        let mut position = 0;
+        highlighted.push_str(&line_prefix);
+        for (range, style) in resolved_spans {
+            highlighted.push_str(&line[position..range.start]);
+            highlighted.push_str(&apply_style(&line[range.clone()], &style).to_string());
+            highlighted.push_str(&line_prefix);
+            position = range.end;
+        }
+        highlighted.push_str(&line[position..]);
+        highlighted.push_str(&line_reset);
+        highlighted.push_str(line_ending);
+
+        Cow::Owned(highlighted)
+    }
+
+    fn update_rule_set(&mut self) {
+        self.rule_set = PatternSet::new(self.rules.iter().map(|rule| &rule.regex));
     }
 
     #[cfg(test)]
@@ -80,30 +295,167 @@ impl LineHighlighter {
     }
 }
 
-fn apply_style(text: &str, text_color: &Color, text_style: &Style) -> ColoredString {
-    let colored_text: ColoredString = match text_color {
-        Color::Red => text.red(),
-        Color::BrightRed => text.bright_red(),
-        Color::Green => text.green(),
-        Color::Yellow => text.yellow(),
-        Color::Blue => text.blue(),
-        Color::Cyan => text.cyan(),
+pub fn apply_line_style(text: &str, line_style: &TextStyle) -> String {
+    // Re-open the line style after every reset so nested highlights don't end it early.
+    let Some((prefix, reset)) = style_codes(line_style) else {
+        return text.to_string();
     };
 
-    let styled_text: ColoredString = match text_style {
-        Style::Bold => colored_text.bold(),
-        Style::Italic => colored_text.italic(),
-        Style::Underline => colored_text.underline(),
-        Style::Normal => colored_text,
-    };
+    let (line, line_ending) = split_line_ending(text);
+    format!("{prefix}{}{reset}{line_ending}", line.replace(&reset, &format!("{reset}{prefix}")))
+}
+
+// Splits off a trailing line break, which cat mode keeps on its lines, so styles are reset before it
+// rather than at the start of the next line.
+pub fn split_line_ending(text: &str) -> (&str, &str) {
+    let line = text.strip_suffix('\n').unwrap_or(text);
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    text.split_at(line.len())
+}
+
+// The escape codes that open and close a style, or None when colors are disabled.
+fn style_codes(text_style: &TextStyle) -> Option<(String, String)> {
+    let marker = apply_style("\0", text_style).to_string();
+    let (prefix, reset) = marker.split_once('\0')?;
+    if prefix.is_empty() || reset.is_empty() {
+        return None;
+    }
+
+    Some((prefix.to_string(), reset.to_string()))
+}
+
+// Keeps `spans` sorted by position, dropping an empty range or one that overlaps a span already taken.
+fn insert_span<'a>(
+    spans: &mut Vec<(Range<usize>, Cow<'a, TextStyle>)>,
+    range: Range<usize>,
+    style: Cow<'a, TextStyle>,
+) {
+    if range.is_empty() {
+        return;
+    }
+
+    let index = spans.partition_point(|(taken, _)| taken.start < range.start);
+    let overlaps_previous = index > 0 && spans[index - 1].0.end > range.start;
+    let overlaps_next = spans.get(index).is_some_and(|(taken, _)| taken.start < range.end);
+    if !overlaps_previous && !overlaps_next {
+        spans.insert(index, (range, style));
+    }
+}
+
+fn apply_style(text: &str, text_style: &TextStyle) -> ColoredString {
+    let mut styled_text = text.normal();
+    if let Some(foreground) = text_style.foreground {
+        styled_text = styled_text.color(foreground);
+    }
+    if let Some(background) = text_style.background {
+        styled_text = styled_text.on_color(background);
+    }
+
+    for style in &text_style.styles {
+        styled_text = match style {
+            Style::Bold => styled_text.bold(),
+            Style::Dimmed => styled_text.dimmed(),
+            Style::Italic => styled_text.italic(),
+            Style::Underline => styled_text.underline(),
+            Style::Reversed => styled_text.reversed(),
+            Style::Blink => styled_text.blink(),
+        };
+    }
 
     styled_text
 }
 
+// Scopes are "match" (the default), "line", or "group:N" / "group:NAME" for a capture group.
+fn parse_scope(scope: &str, regex: &Regex) -> Result<HighlightScope> {
+    match scope {
+        "match" => return Ok(HighlightScope::Match),
+        "line" => return Ok(HighlightScope::Line),
+        _ => {}
+    }
+
+    let Some(group) = scope.strip_prefix("group:") else {
+        bail!("Invalid highlight scope: {scope}, expected match, line or group:N");
+    };
+    let index = match group.parse::<usize>() {
+        Ok(index) => Some(index).filter(|&index| index < regex.captures_len()),
+        Err(_) => regex.capture_names().position(|name| name == Some(group)),
+    };
+
+    index
+        .map(HighlightScope::Group)
+        .with_context(|| format!("Highlight pattern {} has no capture group {group}", regex.as_str()))
+}
+
+fn fnv1a_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn parse_style(word: &str) -> Option<Style> {
+    match word {
+        "bold" => Some(Style::Bold),
+        "dim" | "dimmed" => Some(Style::Dimmed),
+        "italic" => Some(Style::Italic),
+        "underline" => Some(Style::Underline),
+        "reverse" | "reversed" => Some(Style::Reversed),
+        "blink" => Some(Style::Blink),
+        _ => None,
+    }
+}
+
+fn parse_color(word: &str) -> Result<Color> {
+    let word = word.to_ascii_lowercase();
+    if let Some(hex) = word.strip_prefix('#') {
+        let digits = match hex.len() {
+            3 => hex.chars().flat_map(|digit| [digit, digit]).collect(),
+            6 => hex.to_string(),
+            _ => bail!("Invalid hex color: {word}"),
+        };
+        let channel = |index: usize| u8::from_str_radix(&digits[index..index + 2], 16);
+        let (Ok(r), Ok(g), Ok(b)) = (channel(0), channel(2), channel(4)) else {
+            bail!("Invalid hex color: {word}");
+        };
+        return Ok(Color::TrueColor { r, g, b });
+    }
+    if word.bytes().all(|byte| byte.is_ascii_digit()) {
+        return word
+            .parse()
+            .map(Color::AnsiColor)
+            .with_context(|| format!("Invalid 256-color index: {word}"));
+    }
+
+    let color = match word.replace(['-', '_'], "").as_str() {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" | "purple" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "white" => Color::White,
+        "brightblack" | "gray" | "grey" => Color::BrightBlack,
+        "brightred" => Color::BrightRed,
+        "brightgreen" => Color::BrightGreen,
+        "brightyellow" => Color::BrightYellow,
+        "brightblue" => Color::BrightBlue,
+        "brightmagenta" => Color::BrightMagenta,
+        "brightcyan" => Color::BrightCyan,
+        "brightwhite" => Color::BrightWhite,
+        _ => bail!("Unknown color: {word}"),
+    };
+
+    Ok(color)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{apply_style, Color, LineHighlighter, Style};
-    use colored::Colorize;
+    use super::{
+        apply_line_style, apply_style, Color, HighlightRule, HighlightScope, HighlightSpan, LineHighlighter, Style,
+        TextStyle,
+    };
+    use crate::token_highlighter::TokenClass;
+    use colored::{control, Colorize};
 
     #[test]
     fn creates_default_highlight_rules() {
@@ -114,10 +466,180 @@ mod tests {
     #[test]
     fn apply_style_returns_colored_text() {
         let text = "test";
-        let colored_text = apply_style(text, &Color::Red, &Style::Bold);
+        let colored_text = apply_style(text, &TextStyle::new(Color::Red, &[Style::Bold]));
         assert_eq!(colored_text, "test".red().bold());
     }
 
+    #[test]
+    fn parses_combined_styles_and_extended_colors() {
+        let style: TextStyle = "bold underline #ff8800 on black".parse().unwrap();
+        assert_eq!(style.foreground, Some(Color::TrueColor { r: 255, g: 136, b: 0 }));
+        assert_eq!(style.background, Some(Color::Black));
+        assert_eq!(style.styles, vec![Style::Bold, Style::Underline]);
+
+        let style: TextStyle = "208 on #fff".parse().unwrap();
+        assert_eq!(style.foreground, Some(Color::AnsiColor(208)));
+        assert_eq!(style.background, Some(Color::TrueColor { r: 255, g: 255, b: 255 }));
+
+        let style: TextStyle = "Dim Reverse Blink Italic bright-cyan".parse().unwrap();
+        assert_eq!(style.foreground, Some(Color::BrightCyan));
+        assert_eq!(style.styles.len(), 4);
+        assert_eq!("on blue".parse::<TextStyle>().unwrap().foreground, None);
+    }
+
+    #[test]
+    fn rejects_invalid_styles() {
+        for spec in ["", "sparkly", "red blue", "red on", "#ff88", "256", "red on blue on green"] {
+            assert!(spec.parse::<TextStyle>().is_err(), "{spec}");
+        }
+    }
+
+    #[test]
+    fn user_rules_apply_before_default_rules() {
+        let highlighter = LineHighlighter::new()
+            .unwrap()
+            .with_rules(vec![HighlightRule::new("fo+", "magenta on white", "match").unwrap()]);
+        assert_eq!(highlighter.rules().len(), 4);
+        assert_eq!(
+            highlighter.apply("a foo"),
+            "a ".to_string() + &"foo".magenta().on_white().to_string()
+        );
+        assert!(HighlightRule::new("(", "red", "match").is_err());
+    }
+
+    #[test]
+    fn only_rules_matching_the_line_are_applied_from_large_rule_lists() {
+        let rules = (0..300)
+            .map(|n| HighlightRule::new(&format!(r"\bsvc-{n}\b"), "green", "match").unwrap())
+            .collect();
+        let highlighter = LineHighlighter::new().unwrap().with_rules(rules);
+
+        assert_eq!(highlighter.apply("from svc-299 to svc-3000"), format!("from {} to svc-3000", "svc-299".green()));
+        assert!(matches!(highlighter.apply("from svc-3000"), std::borrow::Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn group_scope_highlights_only_the_capture_group() {
+        let rule = HighlightRule::new(r"status=(?P<code>\d+)", "red", "group:code").unwrap();
+        assert_eq!(rule.scope, HighlightScope::Group(1));
+        assert_eq!(HighlightRule::new(r"status=(\d+)", "red", "group:1").unwrap().scope, HighlightScope::Group(1));
+
+        let highlighter = LineHighlighter::new().unwrap().with_rules(vec![rule]);
+        assert_eq!(
+            highlighter.apply("GET status=503 ok"),
+            "GET status=".to_string() + &"503".red().to_string() + " ok"
+        );
+
+        for scope in ["group:2", "group:missing", "everything"] {
+            assert!(HighlightRule::new(r"status=(?P<code>\d+)", "red", scope).is_err(), "{scope}");
+        }
+    }
+
+    #[test]
+    fn rules_never_match_inside_escape_codes_of_other_rules() {
+        control::set_override(true);
+        let highlighter = LineHighlighter::new()
+            .unwrap()
+            .with_rules(vec![HighlightRule::new(r"\d+m", "green", "match").unwrap()]);
+
+        assert_eq!(
+            highlighter.apply("foo took 12ms"),
+            format!("{} took {}s", "foo".bright_red().bold(), "12m".green())
+        );
+        control::unset_override();
+    }
+
+    #[test]
+    fn overlapping_matches_go_to_the_earlier_rule() {
+        let highlighter = LineHighlighter::new().unwrap().with_rules(vec![
+            HighlightRule::new("foo bar", "blue", "match").unwrap(),
+            HighlightRule::new("bar baz", "green", "match").unwrap(),
+        ]);
+
+        assert_eq!(
+            highlighter.apply("foo bar baz"),
+            format!("{} baz", "foo bar".blue())
+        );
+
+        let spans = [HighlightSpan {
+            range: 4..7,
+            style: TextStyle::new(Color::Red, &[]),
+        }];
+        assert_eq!(
+            highlighter.apply_with_spans("foo bar baz", &spans),
+            format!("{} {} baz", "foo".bright_red().bold(), "bar".red())
+        );
+    }
+
+    #[test]
+    fn tokens_only_color_what_other_rules_left_alone() {
+        let highlighter = LineHighlighter::new()
+            .unwrap()
+            .with_rules(vec![HighlightRule::new("took", "blue", "match").unwrap()])
+            .with_tokens(&[TokenClass::Number, TokenClass::Ip]);
+        let number = TextStyle::new(Color::AnsiColor(151), &[]);
+
+        assert_eq!(
+            highlighter.apply("took 12ms"),
+            format!("{} {}", "took".blue(), apply_style("12ms", &number))
+        );
+        assert_eq!(highlighter.rules().len(), 6);
+    }
+
+    #[test]
+    fn hash_colors_are_stable_per_matched_text() {
+        let rule = HighlightRule::new(r"trace=(\w+)", "bold hash", "group:1").unwrap();
+        assert!(rule.color_by_hash);
+        assert_eq!(rule.style.styles, vec![Style::Bold]);
+
+        let first = rule.style_for("4bf92f3577b34da6");
+        assert_eq!(first, rule.style_for("4bf92f3577b34da6"));
+        assert!(matches!(first.foreground, Some(Color::AnsiColor(_))));
+
+        let colors: std::collections::HashSet<_> = (0..50)
+            .map(|id| format!("{:?}", rule.style_for(&format!("request-{id}")).foreground))
+            .collect();
+        assert!(colors.len() > 1);
+
+        assert!(HighlightRule::new("x", "hash", "match").is_ok());
+        assert!(HighlightRule::new("x", "hash red", "match").is_err());
+    }
+
+    #[test]
+    fn added_terms_take_the_next_color_and_win_over_other_rules() {
+        let mut highlighter = LineHighlighter::new().unwrap();
+        highlighter.add_term("foo.1");
+        highlighter.add_term("req-7");
+
+        assert_eq!(
+            highlighter.apply("foo.1 foo req-7 fooX1"),
+            format!(
+                "{} {} {} fooX1",
+                "foo.1".black().on_yellow(),
+                "foo".bright_red().bold(),
+                "req-7".black().on_cyan()
+            )
+        );
+    }
+
+    #[test]
+    fn line_scope_styles_the_whole_line_below_match_highlights() {
+        control::set_override(true);
+        let highlighter = LineHighlighter::new().unwrap().with_rules(vec![
+            HighlightRule::new("panic", "red", "line").unwrap(),
+            HighlightRule::new("boom", "blue", "line").unwrap(),
+        ]);
+        let red = "\x1b[31m";
+
+        assert_eq!(
+            highlighter.apply("panic: foo boom"),
+            format!("{red}panic: \x1b[1;91mfoo\x1b[0m{red} boom\x1b[0m")
+        );
+        assert_eq!(highlighter.apply("just boom"), "just boom".blue().to_string());
+        assert_eq!(highlighter.apply("all good"), "all good");
+        control::unset_override();
+    }
+
     #[test]
     fn highlight_returns_input_when_no_match_exists() {
         let highlighter = LineHighlighter::new().unwrap();
@@ -133,7 +655,9 @@ mod tests {
         let highlighted_line = highlighter.apply(line);
         assert_eq!(
             highlighted_line,
-            "this is a ".to_string() + &apply_style("foo", &Color::BrightRed, &Style::Bold).to_string() + " line"
+            "this is a ".to_string()
+                + &apply_style("foo", &TextStyle::new(Color::BrightRed, &[Style::Bold])).to_string()
+                + " line"
         );
     }
 
@@ -144,12 +668,57 @@ mod tests {
         let highlighted_line = highlighter.apply(line);
         assert_eq!(
             highlighted_line,
-            apply_style("foo", &Color::BrightRed, &Style::Bold).to_string()
+            apply_style("foo", &TextStyle::new(Color::BrightRed, &[Style::Bold])).to_string()
+                + " "
+                + &apply_style("warning", &TextStyle::new(Color::Yellow, &[Style::Underline])).to_string()
                 + " "
-                + &apply_style("warning", &Color::Yellow, &Style::Underline).to_string()
+                + &apply_style("success", &TextStyle::new(Color::Cyan, &[])).to_string()
+        );
+    }
+
+    #[test]
+    fn highlight_with_spans_styles_spans_and_highlights_the_rest() {
+        let highlighter = LineHighlighter::new().unwrap();
+        let line = "GET / 503 foo";
+        let spans = [HighlightSpan {
+            range: 6..9,
+            style: TextStyle::new(Color::Red, &[Style::Bold]),
+        }];
+        assert_eq!(
+            highlighter.apply_with_spans(line, &spans),
+            "GET / ".to_string()
+                + &apply_style("503", &TextStyle::new(Color::Red, &[Style::Bold])).to_string()
                 + " "
-                + &apply_style("success", &Color::Cyan, &Style::Normal).to_string()
+                + &apply_style("foo", &TextStyle::new(Color::BrightRed, &[Style::Bold])).to_string()
+        );
+    }
+
+    #[test]
+    fn line_style_is_reapplied_after_nested_resets() {
+        control::set_override(true);
+        let line = format!("before {} after", "foo".bright_red().bold());
+        let red = "\x1b[31m";
+        assert_eq!(
+            apply_line_style(&line, &TextStyle::new(Color::Red, &[])),
+            format!("{red}before \x1b[1;91mfoo\x1b[0m{red} after\x1b[0m")
+        );
+        control::unset_override();
+    }
+
+    #[test]
+    fn styles_end_before_the_line_break_of_cat_mode_lines() {
+        control::set_override(true);
+        let highlighter = LineHighlighter::new()
+            .unwrap()
+            .with_rules(vec![HighlightRule::new("panic", "red", "line").unwrap()]);
+
+        assert_eq!(highlighter.apply("panic\r\n"), format!("{}\r\n", "panic".red()));
+        assert_eq!(highlighter.apply("foo\n"), format!("{}\n", "foo".bright_red().bold()));
+        assert_eq!(
+            apply_line_style("stderr\n", &TextStyle::new(Color::Red, &[])),
+            format!("{}\n", "stderr".red())
         );
+        control::unset_override();
     }
 
     #[test]
@@ -1,68 +1,237 @@
 use anyhow::Result;
-use regex::Regex;
+use colored::Colorize;
 use std::borrow::Cow;
 
-use crate::line_highlighter::LineHighlighter;
-use crate::line_filter::LineFilter;
+use crate::access_log::{status_highlight, AccessLogParser};
+use crate::field_condition::FieldCondition;
+use crate::field_extractor::FieldExtractor;
+use crate::file_rule::FileRule;
+use crate::fields::Fields;
+use crate::line_highlighter::{split_line_ending, HighlightRule, LineHighlighter};
+use crate::line_filter::{FilterDecision, FilterWord, LineFilter, WordOrigin};
+use crate::token_highlighter::TokenClass;
 
+pub struct ProcessedLine<'a> {
+    pub text: Cow<'a, str>,
+    pub fields: Fields,
+    // Shown by --explain or --show-excluded although the filter rejected it, so it is not a match.
+    pub excluded: bool,
+}
+
+// Explained lines are prefixed with the filter word that decided, and excluded lines are shown dimmed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExplainMode {
+    All,
+    ExcludedOnly,
+}
+
+#[derive(Clone)]
 pub struct LinePipeline {
     filter: LineFilter,
-    line_highlighter: LineHighlighter,
+    explain_mode: Option<ExplainMode>,
+    invert_match: bool,
+    access_log_parser: Option<AccessLogParser>,
+    field_extractor: Option<FieldExtractor>,
+    field_conditions: Vec<FieldCondition>,
+    line_highlighter: Option<LineHighlighter>,
 }
 
 impl LinePipeline {
     pub fn new(
         include_words: Option<Vec<String>>,
         exclude_words: Option<Vec<String>>,
-        disable_preset_excludes: bool,
+        preset_exclude_words: Vec<FilterWord>,
     ) -> Result<Self> {
         Ok(Self {
-            filter: LineFilter::new(include_words, exclude_words, disable_preset_excludes)?,
-            line_highlighter: LineHighlighter::new()?,
+            filter: LineFilter::new(include_words, exclude_words, preset_exclude_words)?,
+            explain_mode: None,
+            invert_match: false,
+            access_log_parser: None,
+            field_extractor: None,
+            field_conditions: Vec::new(),
+            line_highlighter: Some(LineHighlighter::new()?),
         })
     }
 
+    pub fn with_include_words(mut self, include_words: &[FilterWord]) -> Result<Self> {
+        self.filter = self.filter.with_words(include_words, &[])?;
+        Ok(self)
+    }
+
+    pub fn with_explain_mode(mut self, explain_mode: ExplainMode) -> Self {
+        self.explain_mode = Some(explain_mode);
+        self
+    }
+
+    pub fn with_inverted_match(mut self) -> Self {
+        self.invert_match = true;
+        self
+    }
+
+    pub fn with_access_log_parser(mut self, access_log_parser: AccessLogParser) -> Self {
+        self.access_log_parser = Some(access_log_parser);
+        self
+    }
+
+    pub fn with_field_extractor(mut self, field_extractor: FieldExtractor) -> Self {
+        self.field_extractor = Some(field_extractor);
+        self
+    }
+
+    pub fn with_field_conditions(mut self, field_conditions: Vec<FieldCondition>) -> Self {
+        self.field_conditions = field_conditions;
+        self
+    }
+
+    pub fn with_highlight_rules(mut self, highlight_rules: Vec<HighlightRule>) -> Self {
+        self.line_highlighter = self
+            .line_highlighter
+            .map(|line_highlighter| line_highlighter.with_rules(highlight_rules));
+        self
+    }
+
+    pub fn with_token_highlighting(mut self, classes: &[TokenClass]) -> Self {
+        self.line_highlighter = self.line_highlighter.map(|line_highlighter| line_highlighter.with_tokens(classes));
+        self
+    }
+
+    pub fn add_highlight_term(&mut self, term: &str) {
+        if let Some(line_highlighter) = &mut self.line_highlighter {
+            line_highlighter.add_term(term);
+        }
+    }
+
+    // A copy of this pipeline for the files the rules match: their words are added to the filter and
+    // their highlight rules win over the shared ones.
+    pub fn with_file_rules(&self, file_rules: &[&FileRule]) -> Result<Self> {
+        let mut pipeline = self.clone();
+        let mut highlight_rules = Vec::new();
+        for file_rule in file_rules {
+            let origin = WordOrigin::FileRule(file_rule.glob().to_string());
+            let words = |words: &[String]| -> Vec<FilterWord> {
+                words.iter().map(|word| FilterWord::new(word, origin.clone())).collect()
+            };
+            pipeline.filter = pipeline
+                .filter
+                .with_words(&words(&file_rule.include_words), &words(&file_rule.exclude_words))?;
+            for highlight in &file_rule.highlight {
+                highlight_rules.push(highlight.rule()?);
+            }
+        }
+
+        Ok(pipeline.with_highlight_rules(highlight_rules))
+    }
+
+    pub fn without_highlighting(mut self) -> Self {
+        self.line_highlighter = None;
+        self
+    }
+
+    #[cfg(test)]
     pub fn process<'a>(&self, line: &'a str) -> Option<Cow<'a, str>> {
-        if self.filter.allows(line) {
-            Some(self.line_highlighter.apply(line))
-        } else {
-            None
+        self.process_line(line).map(|processed| processed.text)
+    }
+
+    #[cfg(test)]
+    pub fn process_line<'a>(&self, line: &'a str) -> Option<ProcessedLine<'a>> {
+        self.process_record(line, Fields::default())
+    }
+
+    pub fn process_record<'a>(&self, line: &'a str, mut fields: Fields) -> Option<ProcessedLine<'a>> {
+        let mut explanation = None;
+        let passes_filter = match self.explain_mode {
+            None => self.filter.allows(line),
+            Some(explain_mode) => {
+                let decision = self.filter.decide(line);
+                let shown = explain_mode == ExplainMode::All || !decision.allows();
+                if decision == FilterDecision::Empty || !shown {
+                    return None;
+                }
+                // Excluded lines are neither parsed nor highlighted.
+                if !decision.allows() {
+                    let reason = decision.reason().unwrap_or_default();
+                    let (line, line_ending) = split_line_ending(line);
+                    let text = format!("{}{line_ending}", format!("[{reason}] {line}").dimmed());
+                    return Some(ProcessedLine {
+                        text: Cow::Owned(text),
+                        fields,
+                        excluded: true,
+                    });
+                }
+                explanation = decision.reason();
+                true
+            }
+        };
+        // An inverted match still needs the fields of rejected lines to check the conditions.
+        if !passes_filter && (!self.invert_match || line.is_empty()) {
+            return None;
+        }
+
+        let mut highlight_spans = Vec::new();
+        if let Some(access_log_parser) = &self.access_log_parser {
+            if let Some(status_range) = access_log_parser.parse(line, &mut fields) {
+                highlight_spans.push(status_highlight(line, status_range));
+            }
         }
+        if let Some(field_extractor) = &self.field_extractor {
+            field_extractor.extract(line, &mut fields);
+        }
+        let passes_conditions = self.field_conditions.iter().all(|condition| condition.matches(&fields));
+        if (passes_filter && passes_conditions) == self.invert_match {
+            return None;
+        }
+
+        let text = match &self.line_highlighter {
+            Some(line_highlighter) => line_highlighter.apply_with_spans(line, &highlight_spans),
+            None => Cow::Borrowed(line),
+        };
+        let text = match explanation {
+            Some(reason) => Cow::Owned(format!("{} {text}", format!("[{reason}]").dimmed())),
+            None => text,
+        };
+
+        Some(ProcessedLine {
+            text,
+            fields,
+            excluded: false,
+        })
     }
 
-    pub fn include_regex(&self) -> &Option<Regex> {
-        self.filter.include_regex()
+    pub fn include_words(&self) -> &[FilterWord] {
+        self.filter.include_words()
     }
 
-    pub fn exclude_regex(&self) -> &Option<Regex> {
-        self.filter.exclude_regex()
+    pub fn exclude_words(&self) -> &[FilterWord] {
+        self.filter.exclude_words()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config_file::HighlightConfig;
+    use crate::constants::preset_words;
     use colored::{control, Colorize};
 
     #[test]
-    fn new_uses_default_exclude_regex() {
-        let pipeline = LinePipeline::new(None, None, false).unwrap();
-        assert!(pipeline.include_regex().is_none());
-        assert!(pipeline.exclude_regex().is_some());
+    fn new_uses_default_exclude_words() {
+        let pipeline = LinePipeline::new(None, None, preset_words()).unwrap();
+        assert!(pipeline.include_words().is_empty());
+        assert!(!pipeline.exclude_words().is_empty());
     }
 
     #[test]
-    fn new_keeps_include_and_default_exclude_regex() {
-        let pipeline = LinePipeline::new(Some(vec!["include".to_string()]), None, false).unwrap();
-        assert!(pipeline.include_regex().is_some());
-        assert!(pipeline.exclude_regex().is_some());
+    fn new_keeps_include_and_default_exclude_words() {
+        let pipeline = LinePipeline::new(Some(vec!["include".to_string()]), None, preset_words()).unwrap();
+        assert!(!pipeline.include_words().is_empty());
+        assert!(!pipeline.exclude_words().is_empty());
     }
 
     #[test]
-    fn new_with_excludes_keeps_exclude_regex() {
-        let pipeline = LinePipeline::new(None, Some(vec!["exclude".to_string()]), false).unwrap();
-        assert!(pipeline.include_regex().is_none());
-        assert!(pipeline.exclude_regex().is_some());
+    fn new_with_excludes_keeps_exclude_words() {
+        let pipeline = LinePipeline::new(None, Some(vec!["exclude".to_string()]), preset_words()).unwrap();
+        assert!(pipeline.include_words().is_empty());
+        assert!(!pipeline.exclude_words().is_empty());
     }
 
     #[test]
@@ -70,44 +239,44 @@ mod tests {
         let pipeline = LinePipeline::new(
             Some(vec!["include".to_string()]),
             Some(vec!["exclude".to_string()]),
-            true,
+            Vec::new(),
         )
         .unwrap();
-        assert!(pipeline.include_regex().is_some());
-        assert!(pipeline.exclude_regex().is_some());
+        assert!(!pipeline.include_words().is_empty());
+        assert!(!pipeline.exclude_words().is_empty());
     }
 
     #[test]
     fn process_returns_input_when_no_filters_match() {
-        let pipeline = LinePipeline::new(None, None, true).unwrap();
+        let pipeline = LinePipeline::new(None, None, Vec::new()).unwrap();
         let line = "this is a test line";
         assert_eq!(pipeline.process(line), Some(Cow::from(line)));
     }
 
     #[test]
     fn process_returns_input_when_include_matches() {
-        let pipeline = LinePipeline::new(Some(vec!["test".to_string()]), None, true).unwrap();
+        let pipeline = LinePipeline::new(Some(vec!["test".to_string()]), None, Vec::new()).unwrap();
         let line = "this is a test line";
         assert_eq!(pipeline.process(line), Some(Cow::from(line)));
     }
 
     #[test]
     fn process_returns_none_when_include_does_not_match() {
-        let pipeline = LinePipeline::new(Some(vec!["other".to_string()]), None, true).unwrap();
+        let pipeline = LinePipeline::new(Some(vec!["other".to_string()]), None, Vec::new()).unwrap();
         let line = "this is a test line";
         assert!(pipeline.process(line).is_none());
     }
 
     #[test]
     fn process_returns_none_when_exclude_matches() {
-        let pipeline = LinePipeline::new(None, Some(vec!["test".to_string()]), true).unwrap();
+        let pipeline = LinePipeline::new(None, Some(vec!["test".to_string()]), Vec::new()).unwrap();
         let line = "this is a test line";
         assert!(pipeline.process(line).is_none());
     }
 
     #[test]
     fn process_returns_input_when_exclude_does_not_match() {
-        let pipeline = LinePipeline::new(None, Some(vec!["other".to_string()]), true).unwrap();
+        let pipeline = LinePipeline::new(None, Some(vec!["other".to_string()]), Vec::new()).unwrap();
         let line = "this is a test line";
         assert_eq!(pipeline.process(line), Some(Cow::from(line)));
     }
@@ -115,7 +284,7 @@ mod tests {
     #[test]
     fn process_returns_none_when_include_and_exclude_both_match() {
         let pipeline =
-            LinePipeline::new(Some(vec!["test".to_string()]), Some(vec!["line".to_string()]), true).unwrap();
+            LinePipeline::new(Some(vec!["test".to_string()]), Some(vec!["line".to_string()]), Vec::new()).unwrap();
         let line = "this is a test line";
         assert!(pipeline.process(line).is_none());
     }
@@ -123,16 +292,138 @@ mod tests {
     #[test]
     fn process_applies_highlighting_to_allowed_lines() {
         control::set_override(true);
-        let pipeline = LinePipeline::new(None, None, true).unwrap();
+        let pipeline = LinePipeline::new(None, None, Vec::new()).unwrap();
         let line = "this is a foo line";
         let expected = "this is a ".to_string() + &"foo".bright_red().bold().to_string() + " line";
         assert_eq!(pipeline.process(line), Some(Cow::from(expected)));
         control::unset_override();
     }
 
+    #[test]
+    fn process_skips_highlighting_when_disabled() {
+        control::set_override(true);
+        let pipeline = LinePipeline::new(None, None, Vec::new()).unwrap().without_highlighting();
+        let line = "this is a foo line";
+        assert!(matches!(pipeline.process(line), Some(Cow::Borrowed(text)) if text == line));
+        control::unset_override();
+    }
+
+    #[test]
+    fn process_line_exposes_extracted_fields() {
+        let pipeline = LinePipeline::new(None, None, Vec::new())
+            .unwrap()
+            .with_field_extractor(FieldExtractor::new(&[r"(?P<status>\d{3}) (?P<latency>\d+)ms".to_string()]).unwrap());
+        let processed = pipeline.process_line("GET /api 200 12ms").unwrap();
+
+        assert_eq!(processed.fields.get("status"), Some("200"));
+        assert_eq!(processed.fields.get("latency"), Some("12"));
+    }
+
+    #[test]
+    fn process_line_applies_field_conditions() {
+        let pipeline = LinePipeline::new(None, None, Vec::new())
+            .unwrap()
+            .with_field_extractor(FieldExtractor::new(&[r"(?P<latency>\d+)ms".to_string()]).unwrap())
+            .with_field_conditions(vec!["latency>250".parse().unwrap()]);
+
+        assert!(pipeline.process_line("slow request 400ms").is_some());
+        assert!(pipeline.process_line("fast request 12ms").is_none());
+        assert!(pipeline.process_line("no latency at all").is_none());
+    }
+
+    #[test]
+    fn process_line_parses_access_logs_into_fields() {
+        let pipeline = LinePipeline::new(None, None, Vec::new())
+            .unwrap()
+            .with_access_log_parser(AccessLogParser::new("common").unwrap())
+            .with_field_conditions(vec!["status>=500".parse().unwrap()]);
+        let line = r#"127.0.0.1 - - [10/Oct/2024:13:55:36 +0000] "GET /health HTTP/1.1" 502 12"#;
+
+        let processed = pipeline.process_line(line).unwrap();
+        assert_eq!(processed.fields.get("path"), Some("/health"));
+        assert!(pipeline.process_line(&line.replace(" 502 ", " 200 ")).is_none());
+    }
+
+    #[test]
+    fn process_record_keeps_envelope_fields_for_conditions() {
+        let pipeline = LinePipeline::new(None, None, Vec::new())
+            .unwrap()
+            .with_field_conditions(vec!["stream=stderr".parse().unwrap()]);
+        let mut fields = Fields::default();
+        fields.insert("stream", "stderr");
+
+        let processed = pipeline.process_record("panic: boom", fields).unwrap();
+        assert_eq!(processed.fields.get("stream"), Some("stderr"));
+        assert!(pipeline.process_line("panic: boom").is_none());
+    }
+
+    #[test]
+    fn file_rules_extend_a_copy_of_the_pipeline() {
+        control::set_override(true);
+        let pipeline = LinePipeline::new(None, None, Vec::new()).unwrap();
+        let mut file_rule: FileRule = "app.log:-e heartbeat".parse().unwrap();
+        file_rule.highlight = vec![HighlightConfig {
+            pattern: r"user=\w+".to_string(),
+            style: "green".to_string(),
+            scope: "match".to_string(),
+        }];
+
+        let file_pipeline = pipeline.with_file_rules(&[&file_rule]).unwrap();
+        assert!(file_pipeline.process("heartbeat ok").is_none());
+        assert!(pipeline.process("heartbeat ok").is_some());
+        assert_eq!(
+            file_pipeline.process("login user=alice").unwrap(),
+            format!("login {}", "user=alice".green())
+        );
+        control::unset_override();
+    }
+
+    #[test]
+    fn explain_mode_prefixes_lines_with_the_deciding_word() {
+        control::set_override(true);
+        let pipeline = LinePipeline::new(Some(vec!["GET".to_string()]), None, preset_words())
+            .unwrap()
+            .with_explain_mode(ExplainMode::All);
+        assert_eq!(
+            pipeline.process("GET /a\n").unwrap(),
+            format!("{} GET /a\n", "[included by \"GET\" from command line]".dimmed())
+        );
+        assert_eq!(
+            pipeline.process("GET /aaa").unwrap(),
+            "[excluded by \"aaa\" from preset of profile default] GET /aaa".dimmed().to_string()
+        );
+        assert_eq!(
+            pipeline.process("POST /a\n").unwrap(),
+            format!("{}\n", "[excluded: no include word matched] POST /a".dimmed())
+        );
+        assert!(pipeline.process("").is_none());
+        assert!(pipeline.process_line("POST /a").unwrap().excluded);
+        assert!(!pipeline.process_line("GET /a").unwrap().excluded);
+
+        let excluded_only = pipeline.with_explain_mode(ExplainMode::ExcludedOnly);
+        assert!(excluded_only.process("GET /a").is_none());
+        assert!(excluded_only.process("GET /aaa").is_some());
+        control::unset_override();
+    }
+
+    #[test]
+    fn inverted_match_negates_filter_and_conditions() {
+        let pipeline = LinePipeline::new(None, Some(vec!["DEBUG".to_string()]), Vec::new())
+            .unwrap()
+            .with_field_extractor(FieldExtractor::new(&[r"took (?P<ms>\d+)ms".to_string()]).unwrap())
+            .with_field_conditions(vec!["ms>100".parse().unwrap()])
+            .without_highlighting()
+            .with_inverted_match();
+
+        assert_eq!(pipeline.process("DEBUG took 500ms").unwrap(), "DEBUG took 500ms");
+        assert_eq!(pipeline.process("INFO took 5ms").unwrap(), "INFO took 5ms");
+        assert!(pipeline.process("INFO took 500ms").is_none());
+        assert!(pipeline.process("").is_none());
+    }
+
     #[test]
     fn process_returns_none_for_empty_string() {
-        let pipeline = LinePipeline::new(None, None, false).unwrap();
+        let pipeline = LinePipeline::new(None, None, Vec::new()).unwrap();
         let line = "";
         assert!(pipeline.process(line).is_none());
     }
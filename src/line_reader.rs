@@ -0,0 +1,62 @@
+use std::io;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+
+const MAX_LINE_LENGTH: usize = 1024 * 1024;
+
+// Like `lines()`, but invalid UTF-8 is replaced instead of ending the stream, and lines longer than the
+// maximum are split, so a writer that never sends a line break cannot make memory grow without bound.
+pub struct LossyLines<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    max_length: usize,
+}
+
+impl<R: AsyncBufRead + Unpin> LossyLines<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_max_length(reader, MAX_LINE_LENGTH)
+    }
+
+    fn with_max_length(reader: R, max_length: usize) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+            max_length,
+        }
+    }
+
+    pub async fn next_line(&mut self) -> io::Result<Option<String>> {
+        self.buffer.clear();
+        let mut limited = (&mut self.reader).take(self.max_length as u64);
+        if limited.read_until(b'\n', &mut self.buffer).await? == 0 {
+            return Ok(None);
+        }
+
+        let line = self.buffer.strip_suffix(b"\n").unwrap_or(&self.buffer);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        Ok(Some(String::from_utf8_lossy(line).into_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LossyLines;
+
+    #[tokio::test]
+    async fn replaces_invalid_utf8_and_keeps_reading() {
+        let mut lines = LossyLines::new(&b"ok\r\n\xff bad\nafter"[..]);
+
+        assert_eq!(lines.next_line().await.unwrap().as_deref(), Some("ok"));
+        assert_eq!(lines.next_line().await.unwrap().as_deref(), Some("\u{fffd} bad"));
+        assert_eq!(lines.next_line().await.unwrap().as_deref(), Some("after"));
+        assert_eq!(lines.next_line().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn splits_lines_longer_than_the_maximum() {
+        let mut lines = LossyLines::with_max_length(&b"abcdefg\nh\n"[..], 4);
+
+        assert_eq!(lines.next_line().await.unwrap().as_deref(), Some("abcd"));
+        assert_eq!(lines.next_line().await.unwrap().as_deref(), Some("efg"));
+        assert_eq!(lines.next_line().await.unwrap().as_deref(), Some("h"));
+    }
+}
@@ -0,0 +1,212 @@
+use anyhow::{bail, Context, Result};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use tokio::io::BufReader;
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::mpsc;
+
+use crate::fields::Fields;
+use crate::line_reader::LossyLines;
+
+const MAX_DATAGRAM_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ListenAddress {
+    Udp(String),
+    Tcp(String),
+}
+
+impl FromStr for ListenAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(address: &str) -> Result<Self> {
+        if let Some(host_port) = address.strip_prefix("udp://") {
+            return Ok(Self::Udp(host_port.to_string()));
+        }
+        if let Some(host_port) = address.strip_prefix("tcp://") {
+            return Ok(Self::Tcp(host_port.to_string()));
+        }
+
+        bail!("Invalid listen address: {address}, expected udp://HOST:PORT or tcp://HOST:PORT")
+    }
+}
+
+pub struct ReceivedLine {
+    pub peer: SocketAddr,
+    pub line: String,
+}
+
+impl ReceivedLine {
+    // The peer address only goes into the fields, so filter words never match it.
+    pub fn into_record(self) -> (String, Fields) {
+        let mut fields = Fields::default();
+        fields.insert("peer", self.peer.to_string());
+
+        let message = match parse_syslog_priority(&self.line) {
+            Some((priority, message)) => {
+                fields.insert("facility", (priority / 8).to_string());
+                fields.insert("severity", (priority % 8).to_string());
+                message.to_string()
+            }
+            None => self.line,
+        };
+
+        (message, fields)
+    }
+}
+
+pub async fn bind(address: &ListenAddress, sender: mpsc::Sender<ReceivedLine>) -> Result<SocketAddr> {
+    match address {
+        ListenAddress::Udp(host_port) => {
+            let socket = UdpSocket::bind(host_port)
+                .await
+                .with_context(|| format!("Failed to listen on udp://{host_port}"))?;
+            let local_addr = socket.local_addr()?;
+            tokio::spawn(receive_datagrams(socket, sender));
+            Ok(local_addr)
+        }
+        ListenAddress::Tcp(host_port) => {
+            let listener = TcpListener::bind(host_port)
+                .await
+                .with_context(|| format!("Failed to listen on tcp://{host_port}"))?;
+            let local_addr = listener.local_addr()?;
+            tokio::spawn(accept_connections(listener, sender));
+            Ok(local_addr)
+        }
+    }
+}
+
+async fn receive_datagrams(socket: UdpSocket, sender: mpsc::Sender<ReceivedLine>) {
+    let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+    while let Ok((length, peer)) = socket.recv_from(&mut buffer).await {
+        let datagram = String::from_utf8_lossy(&buffer[..length]);
+        for line in datagram.lines().filter(|line| !line.is_empty()) {
+            let line = line.to_string();
+            if sender.send(ReceivedLine { peer, line }).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+async fn accept_connections(listener: TcpListener, sender: mpsc::Sender<ReceivedLine>) {
+    while let Ok((stream, peer)) = listener.accept().await {
+        tokio::spawn(receive_lines(stream, peer, sender.clone()));
+    }
+}
+
+async fn receive_lines(stream: TcpStream, peer: SocketAddr, sender: mpsc::Sender<ReceivedLine>) {
+    let mut lines = LossyLines::new(BufReader::new(stream));
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => return,
+            Err(e) => {
+                eprintln!("Error: Failed to read from {peer}: {e:?}");
+                return;
+            }
+        };
+        if sender.send(ReceivedLine { peer, line }).await.is_err() {
+            return;
+        }
+    }
+}
+
+fn parse_syslog_priority(line: &str) -> Option<(u8, &str)> {
+    let (priority, message) = line.strip_prefix('<')?.split_once('>')?;
+    if priority.is_empty() || priority.len() > 3 {
+        return None;
+    }
+    let priority = priority.parse::<u8>().ok().filter(|&priority| priority <= 191)?;
+
+    // RFC 5424 messages follow the priority with their version, which is always 1, as in "<165>1 2024-...".
+    Some((priority, message.strip_prefix("1 ").unwrap_or(message)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bind, parse_syslog_priority, ListenAddress, ReceivedLine};
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::{TcpStream, UdpSocket};
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn parses_listen_addresses() {
+        assert_eq!(
+            "udp://0.0.0.0:5514".parse::<ListenAddress>().unwrap(),
+            ListenAddress::Udp("0.0.0.0:5514".to_string())
+        );
+        assert_eq!(
+            "tcp://localhost:6000".parse::<ListenAddress>().unwrap(),
+            ListenAddress::Tcp("localhost:6000".to_string())
+        );
+        assert!("http://localhost:80".parse::<ListenAddress>().is_err());
+    }
+
+    #[test]
+    fn strips_syslog_priority_into_fields() {
+        assert_eq!(
+            parse_syslog_priority("<34>Oct 11 22:14:15 host su: failed"),
+            Some((34, "Oct 11 22:14:15 host su: failed"))
+        );
+        assert_eq!(parse_syslog_priority("<999>nope"), None);
+        assert_eq!(parse_syslog_priority("plain text"), None);
+        assert_eq!(parse_syslog_priority("<13>1 2024-01-01T00:00:00Z host"), Some((13, "2024-01-01T00:00:00Z host")));
+        assert_eq!(parse_syslog_priority("<13>10 apples"), Some((13, "10 apples")));
+        assert_eq!(parse_syslog_priority("<13>2024-01-01 host"), Some((13, "2024-01-01 host")));
+
+        let (line, fields) = ReceivedLine {
+            peer: "127.0.0.1:40000".parse().unwrap(),
+            line: "<165>1 2024-01-01T00:00:00Z host app - - - started".to_string(),
+        }
+        .into_record();
+        assert_eq!(line, "2024-01-01T00:00:00Z host app - - - started");
+        assert_eq!(fields.get("peer"), Some("127.0.0.1:40000"));
+        assert_eq!(fields.get("facility"), Some("20"));
+        assert_eq!(fields.get("severity"), Some("5"));
+    }
+
+    #[tokio::test]
+    async fn receives_udp_datagrams_with_peer_address() {
+        let (sender, mut receiver) = mpsc::channel(16);
+        let address = bind(&ListenAddress::Udp("127.0.0.1:0".to_string()), sender).await.unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        client.send_to(b"first\nsecond\n", address).await.unwrap();
+
+        let first = receiver.recv().await.unwrap();
+        assert_eq!(first.line, "first");
+        assert_eq!(first.peer, client.local_addr().unwrap());
+        assert_eq!(receiver.recv().await.unwrap().line, "second");
+    }
+
+    #[tokio::test]
+    async fn keeps_reading_tcp_lines_after_invalid_utf8() {
+        let (sender, mut receiver) = mpsc::channel(16);
+        let address = bind(&ListenAddress::Tcp("127.0.0.1:0".to_string()), sender).await.unwrap();
+
+        let mut client = TcpStream::connect(address).await.unwrap();
+        client.write_all(b"bad \xff\nafter\n").await.unwrap();
+
+        assert_eq!(receiver.recv().await.unwrap().line, "bad \u{fffd}");
+        assert_eq!(receiver.recv().await.unwrap().line, "after");
+    }
+
+    #[tokio::test]
+    async fn receives_newline_delimited_lines_from_multiple_tcp_clients() {
+        let (sender, mut receiver) = mpsc::channel(16);
+        let address = bind(&ListenAddress::Tcp("127.0.0.1:0".to_string()), sender).await.unwrap();
+
+        let mut first = TcpStream::connect(address).await.unwrap();
+        let mut second = TcpStream::connect(address).await.unwrap();
+        first.write_all(b"from first\n").await.unwrap();
+        second.write_all(b"from second\n").await.unwrap();
+
+        let mut received = [receiver.recv().await.unwrap(), receiver.recv().await.unwrap()];
+        received.sort_by(|a, b| a.line.cmp(&b.line));
+        assert_eq!(received[0].line, "from first");
+        assert_eq!(received[0].peer, first.local_addr().unwrap());
+        assert_eq!(received[1].line, "from second");
+        assert_eq!(received[1].peer, second.local_addr().unwrap());
+    }
+}
@@ -1,49 +1,245 @@
-use anyhow::Result;
-use std::process;
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::fs::OpenOptions;
+use std::process::ExitCode;
 
+mod access_log;
+mod alert;
 mod cli;
+mod color;
 mod config;
+mod config_file;
 mod constants;
+mod container_log;
 mod debug;
+mod duration;
+mod exec;
+mod field_condition;
+mod field_extractor;
+mod fields;
+mod file_rule;
+mod interactive;
+mod journal;
+mod line_dedup;
 mod line_highlighter;
 mod line_filter;
 mod line_pipeline;
+mod line_reader;
+mod listener;
+mod matching;
+mod output;
+mod output_format;
+mod parallel_cat;
+mod read_state;
 mod run;
+mod sampling;
+mod severity;
+mod summary;
+mod token_highlighter;
 mod word_pattern;
 
-use clap::Parser;
-
+use crate::access_log::AccessLogParser;
+use crate::alert::{AlertEvaluator, AlertRule};
 use crate::cli::Args;
 use crate::config::AppConfig;
+use crate::config_file::ConfigFile;
+use crate::field_condition::FieldCondition;
+use crate::field_extractor::FieldExtractor;
+use crate::file_rule::FileRule;
+use crate::interactive::Controls;
+use crate::journal::JournalOptions;
+use crate::line_dedup::LineDeduplicator;
 use crate::line_pipeline::LinePipeline;
+use crate::output::Output;
+use crate::output_format::LineRenderer;
+use crate::read_state::ReadState;
+use crate::run::LineEmitter;
+use crate::sampling::{RateLimiter, Sampler};
+use crate::token_highlighter::TokenClass;
 
 #[tokio::main]
-async fn main() {
-    if let Err(e) = run().await {
-        eprintln!("Error: {e:?}");
-        process::exit(1);
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(exit_code) => exit_code,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            ExitCode::from(2)
+        }
     }
 }
 
-async fn run() -> Result<()> {
-    let args = Args::parse();
-    let config = AppConfig::from(args);
+async fn run() -> Result<ExitCode> {
+    let args = Args::parse_command_line();
+    let mut config = AppConfig::from(args);
+    let config_file = ConfigFile::load(config.config_file.as_deref())?;
+    let profile = config_file.select_profiles(&config.profiles, config.disabled_preset_excludes.as_deref())?;
+    if !config.log_files_given && !profile.files.is_empty() {
+        config.log_files = profile.files.clone();
+    }
+
+    let use_color = config.color.resolve();
+    colored::control::set_override(use_color);
 
-    let pipeline = LinePipeline::new(
+    let mut pipeline = LinePipeline::new(
         config.include_words.clone(),
         config.exclude_words.clone(),
-        config.disable_preset_excludes,
-    )?;
+        profile.preset_exclude_words.clone(),
+    )?
+    .with_include_words(&profile.include_words)?;
+    if let Some(explain_mode) = config.explain_mode {
+        pipeline = pipeline.with_explain_mode(explain_mode);
+    }
+    if config.invert_match {
+        pipeline = pipeline.with_inverted_match();
+    }
+
+    let mut highlight_rules = config_file.highlight_rules()?;
+    highlight_rules.extend(profile.highlight_rules()?);
+    pipeline = pipeline.with_highlight_rules(highlight_rules);
+    // --tokens without a list enables every class; without the flag, the config file decides.
+    let token_classes = match config.token_classes {
+        Some(classes) if classes.is_empty() => TokenClass::ALL.to_vec(),
+        Some(classes) => classes,
+        None => config_file.tokens.clone(),
+    };
+    pipeline = pipeline.with_token_highlighting(&token_classes);
+    if !use_color {
+        pipeline = pipeline.without_highlighting();
+    }
+    // Without --columns, the columns are the fields of the parser, container logs and --extract patterns.
+    let mut default_columns = Vec::new();
+    if let Some(parser) = config.parser.as_ref().or(profile.parser.as_ref()) {
+        let parser = AccessLogParser::new(parser)?;
+        default_columns.extend(parser.field_names().map(str::to_string));
+        pipeline = pipeline.with_access_log_parser(parser);
+    }
+    if config.container_format.is_some() {
+        default_columns.extend(["stream".to_string(), "time".to_string()]);
+    }
+    if !config.extract_patterns.is_empty() {
+        let field_extractor = FieldExtractor::new(&config.extract_patterns)?;
+        default_columns.extend(field_extractor.field_names().map(str::to_string));
+        pipeline = pipeline.with_field_extractor(field_extractor);
+    }
+    let mut columns = config.columns.clone();
+    if columns.is_empty() {
+        for column in default_columns {
+            if !columns.contains(&column) {
+                columns.push(column);
+            }
+        }
+    }
+    if !config.where_conditions.is_empty() {
+        let field_conditions = config
+            .where_conditions
+            .iter()
+            .map(|condition| condition.parse::<FieldCondition>())
+            .collect::<Result<Vec<_>>>()?;
+        pipeline = pipeline.with_field_conditions(field_conditions);
+    }
 
     if config.debug {
-        debug::print_debug_info(&config.log_files, pipeline.include_regex(), pipeline.exclude_regex());
+        debug::print_debug_info(&config.log_files, pipeline.include_words(), pipeline.exclude_words());
     }
 
-    if config.use_cat_mode {
-        run::run_cat(config.log_files, pipeline).await?;
+    let mut emitter = LineEmitter::new(pipeline).with_renderer(LineRenderer::new(config.output_format, columns));
+    let mut file_rules = config
+        .file_rules
+        .iter()
+        .map(|spec| spec.parse::<FileRule>())
+        .collect::<Result<Vec<_>>>()?;
+    file_rules.extend(config_file.file_rules()?);
+    if !file_rules.is_empty() {
+        emitter = emitter.with_file_rules(file_rules);
+    }
+    if let Some(max_count) = config.max_count {
+        emitter = emitter.with_max_count(max_count);
+    }
+    if config.count_only {
+        emitter = emitter.with_count_only();
+    }
+    if config.quiet {
+        emitter = emitter.with_quiet();
+    }
+    if let Some(until_match) = &config.until_match {
+        let until_match =
+            Regex::new(until_match).with_context(|| format!("Invalid --until-match pattern: {until_match}"))?;
+        emitter = emitter.with_until_match(until_match, config.timeout);
+    }
+    if let Some(dedup_window) = config.dedup_window {
+        emitter = emitter.with_dedup(LineDeduplicator::new(dedup_window));
+    }
+    if let Some(sample_rate) = config.sample_rate {
+        emitter = emitter.with_sampler(Sampler::new(sample_rate));
+    }
+    if let Some(max_rate) = config.max_rate {
+        emitter = emitter.with_rate_limiter(RateLimiter::new(max_rate));
+    }
+    if let Some(keep_level) = config.keep_level {
+        emitter = emitter.with_keep_level(keep_level);
+    }
+    if let Some(container_format) = config.container_format {
+        emitter = emitter.with_container_format(container_format);
+    }
+    if config.color_stderr {
+        emitter = emitter.with_stderr_color();
+    }
+    let mut output = if config.use_pager {
+        Output::paged_on_overflow()
+    } else if config.buffer_output {
+        Output::buffered_stdout()
     } else {
-        run::run_watch(config.log_files, pipeline).await?;
+        Output::stdout()
+    };
+    if let Some(tee_file) = &config.tee_file {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(tee_file)
+            .with_context(|| format!("Failed to open tee file: {}", tee_file.display()))?;
+        output = output.with_tee(file);
     }
+    emitter = emitter.with_output(output);
+    if config.use_keyboard_controls {
+        if let Some(controls) = Controls::open()? {
+            emitter = emitter.with_controls(controls);
+        }
+    }
+    if config.summary {
+        emitter = emitter.with_summary();
+    }
+    if !config.alerts.is_empty() {
+        let rules = config
+            .alerts
+            .iter()
+            .map(|spec| spec.parse::<AlertRule>())
+            .collect::<Result<Vec<_>>>()?;
+        emitter = emitter.with_alerts(AlertEvaluator::new(rules));
+    }
+
+    // --exec passes on the command's exit status rather than the grep-like codes of the other modes.
+    if let Some(command_line) = &config.exec_command {
+        return Ok(ExitCode::from(run::run_exec(command_line, emitter).await?));
+    }
+
+    // Like grep: 0 when something matched, 1 when nothing did and 2 on errors. Following only ends when
+    // stopped, which is a success unless --until-match waited for its pattern in vain.
+    let match_based = (config.use_cat_mode && config.listen_addresses.is_empty()) || config.until_match.is_some();
+    let found_match = if !config.listen_addresses.is_empty() {
+        run::run_listen(config.listen_addresses, emitter).await?
+    } else if config.use_journal {
+        let options = JournalOptions {
+            units: config.journal_units,
+            follow: !config.use_cat_mode,
+            cursor_file: config.journal_cursor_file,
+        };
+        run::run_journal(options, emitter).await?
+    } else if config.use_cat_mode {
+        let read_state = config.state_file.map(ReadState::load).transpose()?;
+        run::run_cat(config.log_files, emitter, read_state).await?
+    } else {
+        run::run_watch(config.log_files, emitter).await?
+    };
 
-    Ok(())
+    Ok(if found_match || !match_based { ExitCode::SUCCESS } else { ExitCode::from(1) })
 }
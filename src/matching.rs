@@ -0,0 +1,134 @@
+use aho_corasick::{AhoCorasick, Input, MatchKind};
+use anyhow::Result;
+use regex::{Regex, RegexSet};
+use std::iter;
+
+pub struct WordMatch {
+    pub list: usize,
+    pub index: usize,
+}
+
+// Several lists of literal words in one Aho-Corasick automaton, so a single scan of a line finds the
+// words of all lists. Where words of several lists start at the same position, the earlier list wins,
+// and within a list the word listed first, as in a regex alternation.
+#[derive(Clone)]
+pub struct WordSet {
+    automaton: AhoCorasick,
+    // The list and the position in it of each non-empty word, by pattern ID.
+    words: Vec<(usize, usize)>,
+    non_empty_lists: Vec<bool>,
+}
+
+impl WordSet {
+    pub fn new<S: AsRef<str>>(lists: &[&[S]]) -> Result<Self> {
+        let mut patterns = Vec::new();
+        let mut words = Vec::new();
+        let mut non_empty_lists = vec![false; lists.len()];
+        for (list, list_words) in lists.iter().enumerate() {
+            for (index, word) in list_words.iter().enumerate() {
+                if !word.as_ref().is_empty() {
+                    patterns.push(word.as_ref());
+                    words.push((list, index));
+                    non_empty_lists[list] = true;
+                }
+            }
+        }
+        // Leftmost-first matching lets the automaton use its SIMD prefilter, which overlapping search cannot.
+        let automaton = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostFirst)
+            .build(patterns)?;
+
+        Ok(Self {
+            automaton,
+            words,
+            non_empty_lists,
+        })
+    }
+
+    pub fn has_words(&self, list: usize) -> bool {
+        self.non_empty_lists.get(list).copied().unwrap_or_default()
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        self.automaton.is_match(line)
+    }
+
+    // Reports one word for every position where a word starts, from left to right. The search resumes
+    // right after the start of the previous word, so words overlapping it are found too.
+    pub fn find_iter<'a>(&'a self, line: &'a str) -> impl Iterator<Item = WordMatch> + 'a {
+        let mut position = 0;
+        iter::from_fn(move || {
+            if position > line.len() {
+                return None;
+            }
+            let found = self.automaton.find(Input::new(line).span(position..line.len()))?;
+            position = found.start() + 1;
+
+            let (list, index) = self.words[found.pattern().as_usize()];
+            Some(WordMatch { list, index })
+        })
+    }
+}
+
+// Tells which of many regexes match a line in one pass, so only those need to be searched for spans.
+// Should the patterns be too large to combine, every pattern is reported as a candidate instead.
+#[derive(Clone)]
+pub struct PatternSet {
+    set: Option<RegexSet>,
+    len: usize,
+}
+
+impl PatternSet {
+    pub fn new<'a>(regexes: impl IntoIterator<Item = &'a Regex>) -> Self {
+        let patterns: Vec<_> = regexes.into_iter().map(Regex::as_str).collect();
+
+        Self {
+            set: RegexSet::new(&patterns).ok(),
+            len: patterns.len(),
+        }
+    }
+
+    pub fn matching(&self, line: &str) -> Vec<usize> {
+        match &self.set {
+            Some(set) => set.matches(line).into_iter().collect(),
+            None => (0..self.len).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PatternSet, WordSet};
+    use regex::Regex;
+
+    fn found(words: &WordSet, line: &str) -> Vec<(usize, usize)> {
+        words.find_iter(line).map(|found| (found.list, found.index)).collect()
+    }
+
+    #[test]
+    fn finds_overlapping_words_of_every_list() {
+        let words = WordSet::new(&[&["rro", "timeout"], &["error", ""]]).unwrap();
+
+        assert_eq!(found(&words, "an error, timeout"), vec![(1, 0), (0, 0), (0, 1)]);
+        assert!(words.has_words(0) && words.has_words(1) && !words.has_words(2));
+        assert!(words.is_match("timeout") && !words.is_match("ok"));
+        assert!(found(&words, "").is_empty());
+    }
+
+    #[test]
+    fn earlier_lists_and_words_win_at_the_same_position() {
+        let words = WordSet::new(&[&["foobar", "foo"], &["foo", "bar"]]).unwrap();
+
+        assert_eq!(found(&words, "x foo"), vec![(0, 1)]);
+        assert_eq!(found(&words, "x foobar"), vec![(0, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn reports_the_matching_patterns() {
+        let regexes = [Regex::new(r"\d+ms").unwrap(), Regex::new("GET").unwrap(), Regex::new("^x").unwrap()];
+        let patterns = PatternSet::new(&regexes);
+
+        assert_eq!(patterns.matching("GET took 12ms"), vec![0, 1]);
+        assert!(patterns.matching("POST").is_empty());
+    }
+}
@@ -0,0 +1,373 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, ErrorKind, IsTerminal, Stdout, Write};
+use std::mem;
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+
+const DEFAULT_PAGER: &str = "less -R";
+const BUFFER_CAPACITY: usize = 256 * 1024;
+
+pub struct Output {
+    target: Target,
+    closed: bool,
+    tee: Option<Tee>,
+}
+
+enum Target {
+    Stdout(Stdout),
+    Buffered(BufWriter<Stdout>),
+    Pending(PendingPage),
+    Pager(Pager),
+}
+
+impl Output {
+    pub fn stdout() -> Self {
+        Self {
+            target: Target::Stdout(io::stdout()),
+            closed: false,
+            tee: None,
+        }
+    }
+
+    // For output with an end, such as cat mode: lines are written in large blocks instead of one by one.
+    pub fn buffered_stdout() -> Self {
+        Self {
+            target: buffered_stdout(),
+            closed: false,
+            tee: None,
+        }
+    }
+
+    // Output is held back until it fills the screen; only then is the pager started, so short output
+    // is printed as if no pager was involved.
+    pub fn paged_on_overflow() -> Self {
+        let target = match terminal_size() {
+            Some((rows, columns)) if io::stdout().is_terminal() => Target::Pending(PendingPage::new(rows, columns)),
+            _ => buffered_stdout(),
+        };
+
+        Self {
+            target,
+            closed: false,
+            tee: None,
+        }
+    }
+
+    pub fn with_tee(mut self, file: File) -> Self {
+        self.tee = Some(Tee { file, in_escape: false });
+        self
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    pub fn finish(&mut self) -> Result<()> {
+        let result = match mem::replace(&mut self.target, Target::Stdout(io::stdout())) {
+            Target::Stdout(mut stdout) => stdout.flush(),
+            Target::Buffered(mut stdout) => stdout.flush(),
+            Target::Pending(page) => {
+                let mut stdout = io::stdout();
+                stdout.write_all(&page.buffer).and_then(|()| stdout.flush())
+            }
+            Target::Pager(pager) => return pager.wait(),
+        };
+
+        match result {
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+            result => result.context("Failed to write output"),
+        }
+    }
+
+    fn write_to_target(&mut self, buf: &[u8]) -> io::Result<()> {
+        match &mut self.target {
+            Target::Stdout(stdout) => stdout.write_all(buf),
+            Target::Buffered(stdout) => stdout.write_all(buf),
+            Target::Pager(pager) => pager.stdin.write_all(buf),
+            Target::Pending(page) => {
+                if !page.push(buf) {
+                    return Ok(());
+                }
+
+                let buffer = mem::take(&mut page.buffer);
+                let mut pager = Pager::spawn(&pager_command())?;
+                pager.ignore_interrupt();
+
+                let result = pager.stdin.write_all(&buffer);
+                self.target = Target::Pager(pager);
+                result
+            }
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(tee) = &mut self.tee {
+            tee.write_without_colors(buf)?;
+        }
+        if self.closed {
+            return Ok(buf.len());
+        }
+
+        match self.write_to_target(buf) {
+            Ok(()) => Ok(buf.len()),
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => {
+                self.closed = true;
+                if let Target::Pager(pager) = &mut self.target {
+                    pager.restore_interrupt();
+                    let status = pager.child.wait()?;
+                    check_pager_status(status, &pager.command_line)?;
+                }
+                Ok(buf.len())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.target {
+            Target::Stdout(stdout) => stdout.flush(),
+            Target::Buffered(stdout) => stdout.flush(),
+            Target::Pager(pager) => pager.stdin.flush(),
+            Target::Pending(_) => Ok(()),
+        }
+    }
+}
+
+struct Tee {
+    file: File,
+    in_escape: bool,
+}
+
+impl Tee {
+    fn write_without_colors(&mut self, buf: &[u8]) -> io::Result<()> {
+        let mut text = Vec::with_capacity(buf.len());
+        for &byte in buf {
+            match byte {
+                0x1b => self.in_escape = true,
+                _ if self.in_escape => self.in_escape = !byte.is_ascii_alphabetic(),
+                _ => text.push(byte),
+            }
+        }
+        self.file.write_all(&text)
+    }
+}
+
+struct PendingPage {
+    buffer: Vec<u8>,
+    screen_rows: usize,
+    screen_columns: usize,
+    rows: usize,
+    column: usize,
+    in_escape: bool,
+}
+
+impl PendingPage {
+    fn new(screen_rows: usize, screen_columns: usize) -> Self {
+        Self {
+            buffer: Vec::new(),
+            screen_rows,
+            screen_columns,
+            rows: 0,
+            column: 0,
+            in_escape: false,
+        }
+    }
+
+    // Returns true once the buffered output no longer fits on the screen, counting wrapped lines
+    // and skipping the color escape sequences.
+    fn push(&mut self, buf: &[u8]) -> bool {
+        self.buffer.extend_from_slice(buf);
+
+        for &byte in buf {
+            match byte {
+                0x1b => self.in_escape = true,
+                _ if self.in_escape => self.in_escape = !byte.is_ascii_alphabetic(),
+                b'\n' => {
+                    self.rows += self.column.div_ceil(self.screen_columns).max(1);
+                    self.column = 0;
+                }
+                // UTF-8 continuation bytes do not start a new character.
+                _ if byte & 0xc0 == 0x80 => {}
+                _ => self.column += 1,
+            }
+        }
+
+        self.rows + self.column.div_ceil(self.screen_columns) >= self.screen_rows
+    }
+}
+
+struct Pager {
+    command_line: String,
+    child: Child,
+    stdin: ChildStdin,
+    previous_interrupt_action: Option<libc::sigaction>,
+}
+
+impl Pager {
+    fn spawn(command_line: &str) -> io::Result<Self> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command_line)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().ok_or_else(|| io::Error::other("Failed to open pager input"))?;
+
+        Ok(Self {
+            command_line: command_line.to_string(),
+            child,
+            stdin,
+            previous_interrupt_action: None,
+        })
+    }
+
+    // Like git, leave Ctrl-C to the pager; being killed by it would hand the terminal back to the shell
+    // while the pager is still running.
+    fn ignore_interrupt(&mut self) {
+        // SAFETY: sigaction is a plain C struct for which all zeroes is valid: no flags and an empty mask.
+        let mut ignore: libc::sigaction = unsafe { mem::zeroed() };
+        ignore.sa_sigaction = libc::SIG_IGN;
+        self.previous_interrupt_action = set_interrupt_action(&ignore);
+    }
+
+    // Once the pager is gone, Ctrl-C stops log-viewer again, e.g. while it keeps reading after the pager
+    // was quit early.
+    fn restore_interrupt(&mut self) {
+        if let Some(previous) = self.previous_interrupt_action.take() {
+            set_interrupt_action(&previous);
+        }
+    }
+
+    fn wait(self) -> Result<()> {
+        let Self {
+            command_line,
+            mut child,
+            stdin,
+            previous_interrupt_action,
+        } = self;
+        drop(stdin);
+        let status = child.wait().context("Failed to wait for pager");
+        if let Some(previous) = previous_interrupt_action {
+            set_interrupt_action(&previous);
+        }
+        Ok(check_pager_status(status?, &command_line)?)
+    }
+}
+
+// The pager is started through sh, which exits with 127 when the command is not found and 126 when it
+// cannot be run; the output sent to it is lost then, so this is an error rather than a closed pager.
+fn check_pager_status(status: ExitStatus, command_line: &str) -> io::Result<()> {
+    match status.code() {
+        Some(code @ (126 | 127)) => Err(io::Error::other(format!(
+            "Failed to run pager `{command_line}` (exit status {code}); set PAGER or use --no-pager"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+// Returns the action that was installed before, with its flags, so a handler installed by tokio can be
+// put back exactly as it was.
+fn set_interrupt_action(action: &libc::sigaction) -> Option<libc::sigaction> {
+    // SAFETY: sigaction is a plain C struct for which all zeroes is valid.
+    let mut previous: libc::sigaction = unsafe { mem::zeroed() };
+    // SAFETY: both pointers point to valid sigaction structs, and action is either SIG_IGN or an action
+    // previously returned by sigaction for SIGINT.
+    let result = unsafe { libc::sigaction(libc::SIGINT, action, &mut previous) };
+    (result == 0).then_some(previous)
+}
+
+fn buffered_stdout() -> Target {
+    Target::Buffered(BufWriter::with_capacity(BUFFER_CAPACITY, io::stdout()))
+}
+
+fn pager_command() -> String {
+    env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_PAGER.to_string())
+}
+
+fn terminal_size() -> Option<(usize, usize)> {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCGWINSZ only writes a winsize into the pointer, which points to a valid winsize.
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+
+    (result == 0 && size.ws_row > 0 && size.ws_col > 0).then(|| (usize::from(size.ws_row), usize::from(size.ws_col)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Output, Pager, PendingPage, Target};
+    use std::io::Write;
+
+    #[test]
+    fn pending_page_overflows_once_the_screen_is_full() {
+        let mut page = PendingPage::new(3, 10);
+
+        assert!(!page.push(b"\x1b[1;31mshort\x1b[0m\n"));
+        assert!(!page.push("\u{e9}t\u{e9}\n".as_bytes()));
+        assert!(page.push(b"third line"));
+        assert_eq!(page.buffer.len(), 33);
+
+        let mut wrapping = PendingPage::new(3, 10);
+        assert!(!wrapping.push(b"0123456789\n"));
+        assert!(wrapping.push(b"0123456789012345678901\n"));
+    }
+
+    #[test]
+    fn tee_gets_output_without_colors() {
+        let path = std::env::temp_dir().join(format!("log-viewer-tee-{}", std::process::id()));
+        let mut output = Output {
+            target: Target::Pending(PendingPage::new(100, 80)),
+            closed: false,
+            tee: None,
+        }
+        .with_tee(std::fs::File::create(&path).unwrap());
+
+        write!(output, "plain \x1b[1;3").unwrap();
+        writeln!(output, "1mred\x1b[0m line").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "plain red line\n");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn closed_pager_stops_output_without_error() {
+        let mut pager = Pager::spawn("exit 0").unwrap();
+        pager.child.wait().unwrap();
+        let mut output = Output {
+            target: Target::Pager(pager),
+            closed: false,
+            tee: None,
+        };
+
+        assert!(writeln!(output, "line").is_ok());
+        assert!(output.is_closed());
+        assert!(output.finish().is_ok());
+    }
+
+    #[test]
+    fn missing_pager_is_reported() {
+        let mut pager = Pager::spawn("exit 127").unwrap();
+        pager.child.wait().unwrap();
+        let mut output = Output {
+            target: Target::Pager(pager),
+            closed: false,
+            tee: None,
+        };
+        assert!(writeln!(output, "line").is_err());
+
+        let mut output = Output {
+            target: Target::Pager(Pager::spawn("no-such-pager-command").unwrap()),
+            closed: false,
+            tee: None,
+        };
+        assert!(output.finish().is_err());
+    }
+}
@@ -0,0 +1,120 @@
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+use crate::line_pipeline::ProcessedLine;
+
+const MISSING_FIELD: &str = "-";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    Columns,
+}
+
+pub struct LineRenderer {
+    format: OutputFormat,
+    columns: Vec<String>,
+    widths: Vec<usize>,
+}
+
+impl LineRenderer {
+    pub fn new(format: OutputFormat, columns: Vec<String>) -> Self {
+        Self {
+            format,
+            widths: vec![0; columns.len()],
+            columns,
+        }
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    pub fn render_structured(&mut self, raw_line: &str, processed: &ProcessedLine) -> String {
+        let raw_line = raw_line.trim_end_matches(['\r', '\n']);
+
+        match self.format {
+            OutputFormat::Text => processed.text.trim_end_matches(['\r', '\n']).to_string(),
+            OutputFormat::Json => {
+                let fields: Map<String, Value> = processed
+                    .fields
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), Value::from(value)))
+                    .collect();
+                serde_json::json!({ "line": raw_line, "fields": fields }).to_string()
+            }
+            OutputFormat::Columns => {
+                if self.columns.is_empty() {
+                    self.columns = processed.fields.iter().map(|(name, _)| name.to_string()).collect();
+                    self.widths = vec![0; self.columns.len()];
+                }
+
+                let mut rendered = String::new();
+                for (column, width) in self.columns.iter().zip(self.widths.iter_mut()) {
+                    let value = processed.fields.get(column).unwrap_or(MISSING_FIELD);
+                    *width = (*width).max(value.chars().count());
+                    rendered.push_str(&format!("{value:<width$}  ", width = *width));
+                }
+                rendered.push_str(processed.text.trim_end_matches(['\r', '\n']));
+                rendered
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LineRenderer, OutputFormat};
+    use crate::fields::Fields;
+    use crate::line_pipeline::ProcessedLine;
+    use std::borrow::Cow;
+
+    fn processed_line<'a>(text: &'a str, entries: &[(&str, &str)]) -> ProcessedLine<'a> {
+        let mut fields = Fields::default();
+        for (name, value) in entries {
+            fields.insert(*name, *value);
+        }
+        ProcessedLine {
+            text: Cow::Borrowed(text),
+            fields,
+            excluded: false,
+        }
+    }
+
+    #[test]
+    fn renders_json_with_raw_line_and_fields() {
+        let mut renderer = LineRenderer::new(OutputFormat::Json, Vec::new());
+        let processed = processed_line("GET 503 12ms\n", &[("status", "503"), ("latency", "12")]);
+
+        assert_eq!(
+            renderer.render_structured("GET 503 12ms\n", &processed),
+            r#"{"fields":{"latency":"12","status":"503"},"line":"GET 503 12ms"}"#
+        );
+    }
+
+    #[test]
+    fn renders_columns_aligned_to_widest_value_seen() {
+        let mut renderer = LineRenderer::new(OutputFormat::Columns, vec!["status".to_string(), "latency".to_string()]);
+
+        assert_eq!(
+            renderer.render_structured("a", &processed_line("a", &[("status", "200"), ("latency", "1200")])),
+            "200  1200  a"
+        );
+        assert_eq!(
+            renderer.render_structured("b", &processed_line("b", &[("latency", "7")])),
+            "-    7     b"
+        );
+    }
+
+    #[test]
+    fn columns_default_to_fields_of_first_line() {
+        let mut renderer = LineRenderer::new(OutputFormat::Columns, Vec::new());
+
+        assert_eq!(
+            renderer.render_structured("a", &processed_line("a", &[("user", "bob")])),
+            "bob  a"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use anyhow::Result;
+use regex::Regex;
+use std::num::NonZeroUsize;
+use std::thread;
+
+use crate::fields::Fields;
+use crate::line_pipeline::{LinePipeline, ProcessedLine};
+
+const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+pub struct ChunkLine<'a> {
+    pub line: &'a str,
+    pub processed: ProcessedLine<'a>,
+}
+
+pub struct ProcessedChunk<'a> {
+    pub lines: Vec<ChunkLine<'a>>,
+    pub until_matched: bool,
+}
+
+// Chunks are processed on one thread each and handed to emit in their original order; emit returns
+// false to stop reading.
+pub fn process_in_order<'a, E>(
+    pipeline: &LinePipeline,
+    until_match: Option<&Regex>,
+    text: &'a str,
+    emit: E,
+) -> Result<()>
+where
+    E: FnMut(ProcessedChunk<'a>) -> Result<bool>,
+{
+    process_chunks_in_order(pipeline, until_match, text, CHUNK_SIZE, emit)
+}
+
+fn process_chunks_in_order<'a, E>(
+    pipeline: &LinePipeline,
+    until_match: Option<&Regex>,
+    text: &'a str,
+    chunk_size: usize,
+    mut emit: E,
+) -> Result<()>
+where
+    E: FnMut(ProcessedChunk<'a>) -> Result<bool>,
+{
+    let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let chunks = split_chunks(text, chunk_size);
+
+    thread::scope(|scope| {
+        for batch in chunks.chunks(threads) {
+            let workers: Vec<_> = batch
+                .iter()
+                .map(|&chunk| scope.spawn(move || process_chunk(pipeline, until_match, chunk)))
+                .collect();
+
+            for worker in workers {
+                let chunk = worker.join().expect("line processing thread panicked");
+                if !emit(chunk)? {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+// Splits text into chunks of about chunk_size bytes that end at a line break.
+fn split_chunks(text: &str, chunk_size: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+
+    while start < text.len() {
+        // Line breaks are single bytes, so searching the bytes never splits a character.
+        let newline = text
+            .as_bytes()
+            .get(start + chunk_size..)
+            .and_then(|rest| rest.iter().position(|&byte| byte == b'\n'));
+        let end = match newline {
+            Some(newline) => start + chunk_size + newline + 1,
+            None => text.len(),
+        };
+        chunks.push(&text[start..end]);
+        start = end;
+    }
+    chunks
+}
+
+// Processing stops after the first line matching until_match, like reading line by line does.
+fn process_chunk<'a>(pipeline: &LinePipeline, until_match: Option<&Regex>, chunk: &'a str) -> ProcessedChunk<'a> {
+    let mut lines = Vec::new();
+
+    for line in chunk.split_inclusive('\n') {
+        if let Some(processed) = pipeline.process_record(line, Fields::default()) {
+            lines.push(ChunkLine { line, processed });
+        }
+        if until_match.is_some_and(|until_match| until_match.is_match(line)) {
+            return ProcessedChunk {
+                lines,
+                until_matched: true,
+            };
+        }
+    }
+
+    ProcessedChunk {
+        lines,
+        until_matched: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{process_chunk, process_chunks_in_order, split_chunks};
+    use crate::line_pipeline::LinePipeline;
+    use regex::Regex;
+
+    #[test]
+    fn splits_chunks_at_line_breaks() {
+        let chunks = split_chunks("aaaa\nbb\ncccccc\nd", 3);
+
+        assert_eq!(chunks, vec!["aaaa\n", "bb\ncccccc\n", "d"]);
+        assert_eq!(split_chunks("\u{e9}\u{e9}\n\u{e9}", 1), vec!["\u{e9}\u{e9}\n", "\u{e9}"]);
+        assert!(split_chunks("", 3).is_empty());
+    }
+
+    #[test]
+    fn chunk_processing_stops_at_the_until_match_line() {
+        let pipeline = LinePipeline::new(Some(vec!["GET".to_string()]), None, Vec::new()).unwrap();
+        let until_match = Regex::new("ready").unwrap();
+
+        let chunk = process_chunk(&pipeline, Some(&until_match), "GET /\nready\nGET /late\n");
+        assert_eq!(chunk.lines.len(), 1);
+        assert_eq!(chunk.lines[0].line, "GET /\n");
+        assert!(chunk.until_matched);
+        assert!(!process_chunk(&pipeline, Some(&until_match), "GET /\n").until_matched);
+    }
+
+    #[test]
+    fn chunks_are_emitted_in_their_original_order() {
+        let pipeline = LinePipeline::new(None, None, Vec::new()).unwrap();
+        let text: String = (0..10_000).map(|n| format!("l\u{e9}ne {n}\n")).collect();
+        let mut emitted = Vec::new();
+
+        process_chunks_in_order(&pipeline, None, &text, 100, |chunk| {
+            emitted.extend(chunk.lines.iter().map(|chunk_line| chunk_line.line));
+            Ok(true)
+        })
+        .unwrap();
+
+        assert_eq!(emitted.concat(), text);
+    }
+}
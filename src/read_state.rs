@@ -0,0 +1,124 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FilePosition {
+    inode: u64,
+    offset: u64,
+}
+
+pub struct ReadState {
+    path: PathBuf,
+    positions: BTreeMap<String, FilePosition>,
+}
+
+impl ReadState {
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read state file: {}", path.display())),
+        };
+
+        let positions = contents
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(3, '\t');
+                let inode = parts.next()?.parse().ok()?;
+                let offset = parts.next()?.parse().ok()?;
+                let file_path = parts.next()?;
+                Some((file_path.to_string(), FilePosition { inode, offset }))
+            })
+            .collect();
+
+        Ok(Self { path, positions })
+    }
+
+    pub fn resume_offset(&self, file_path: &str, inode: u64, len: u64) -> u64 {
+        match self.positions.get(file_path) {
+            Some(position) if position.inode == inode && position.offset <= len => position.offset,
+            _ => 0,
+        }
+    }
+
+    pub fn record(&mut self, file_path: &str, inode: u64, offset: u64) {
+        self.positions.insert(file_path.to_string(), FilePosition { inode, offset });
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let contents: String = self
+            .positions
+            .iter()
+            .map(|(file_path, position)| format!("{}\t{}\t{file_path}\n", position.inode, position.offset))
+            .collect();
+
+        let temporary_path = temporary_path(&self.path);
+        fs::write(&temporary_path, contents)
+            .and_then(|()| fs::rename(&temporary_path, &self.path))
+            .with_context(|| format!("Failed to write state file: {}", self.path.display()))
+    }
+}
+
+// The suffix is appended rather than replacing the extension, so state.json and state.toml, or state
+// and state.tmp, never share a temporary file.
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut temporary_path = path.as_os_str().to_os_string();
+    temporary_path.push(".tmp");
+    PathBuf::from(temporary_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{temporary_path, ReadState};
+    use std::path::Path;
+
+    #[test]
+    fn resumes_only_for_same_inode_and_non_shrunk_file() {
+        let mut state = ReadState::load(std::env::temp_dir().join("log-viewer-missing-state")).unwrap();
+        state.record("/var/log/app.log", 42, 1000);
+
+        assert_eq!(state.resume_offset("/var/log/app.log", 42, 1500), 1000);
+        assert_eq!(state.resume_offset("/var/log/app.log", 42, 1000), 1000);
+        assert_eq!(state.resume_offset("/var/log/app.log", 42, 999), 0);
+        assert_eq!(state.resume_offset("/var/log/app.log", 43, 1500), 0);
+        assert_eq!(state.resume_offset("/var/log/other.log", 42, 1500), 0);
+    }
+
+    #[test]
+    fn saves_and_loads_positions() {
+        let path = std::env::temp_dir().join(format!("log-viewer-state-{}", std::process::id()));
+        let mut state = ReadState::load(path.clone()).unwrap();
+        state.record("/var/log/with\ttab.log", 7, 120);
+        state.record("/var/log/app.log", 42, 1000);
+        state.save().unwrap();
+
+        let loaded = ReadState::load(path.clone()).unwrap();
+        assert_eq!(loaded.resume_offset("/var/log/with\ttab.log", 7, 120), 120);
+        assert_eq!(loaded.resume_offset("/var/log/app.log", 42, 1000), 1000);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn temporary_files_keep_the_whole_state_file_name() {
+        assert_eq!(temporary_path(Path::new("/tmp/state")), Path::new("/tmp/state.tmp"));
+        assert_eq!(temporary_path(Path::new("/tmp/state.json")), Path::new("/tmp/state.json.tmp"));
+        assert_eq!(temporary_path(Path::new("/tmp/state.tmp")), Path::new("/tmp/state.tmp.tmp"));
+
+        let path = std::env::temp_dir().join(format!("log-viewer-bare-state-{}", std::process::id()));
+        let tmp_named_path = temporary_path(&path);
+        let mut state = ReadState::load(path.clone()).unwrap();
+        state.record("/var/log/app.log", 1, 10);
+        state.save().unwrap();
+        let mut tmp_named_state = ReadState::load(tmp_named_path.clone()).unwrap();
+        tmp_named_state.record("/var/log/app.log", 2, 20);
+        tmp_named_state.save().unwrap();
+
+        assert_eq!(ReadState::load(path.clone()).unwrap().resume_offset("/var/log/app.log", 1, 10), 10);
+        assert_eq!(ReadState::load(tmp_named_path.clone()).unwrap().resume_offset("/var/log/app.log", 2, 20), 20);
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(tmp_named_path).unwrap();
+    }
+}
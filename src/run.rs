@@ -1,33 +1,522 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
 use linemux::MuxedLines;
+use memmap2::Mmap;
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::{SeekFrom, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
 use tokio::fs::File;
-use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::io::{AsyncBufReadExt, AsyncSeekExt, BufReader};
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::sync::mpsc;
 
-use crate::line_pipeline::LinePipeline;
+use crate::alert::AlertEvaluator;
+use crate::container_log::{ContainerFormat, ContainerLogDecoder};
+use crate::exec::{self, ExecLine};
+use crate::fields::Fields;
+use crate::file_rule::FileRule;
+use crate::interactive::{self, Command, Controls};
+use crate::journal::{JournalEntry, JournalOptions};
+use crate::line_dedup::{repeat_notice, DedupOutcome, LineDeduplicator};
+use crate::line_highlighter::{apply_line_style, Color, TextStyle};
+use crate::line_pipeline::{LinePipeline, ProcessedLine};
+use crate::listener::{self, ListenAddress};
+use crate::output::Output;
+use crate::output_format::{LineRenderer, OutputFormat};
+use crate::parallel_cat;
+use crate::read_state::ReadState;
+use crate::sampling::{dropped_notice, RateLimiter, Sampler};
+use crate::severity::Severity;
+use crate::summary::Summary;
 
 enum OutputMode {
     PreserveExistingLineEnding,
     AppendLineEnding,
 }
 
-pub async fn run_cat(log_files: Vec<String>, pipeline: LinePipeline) -> Result<()> {
+pub struct LineEmitter {
+    pipeline: LinePipeline,
+    file_rules: Vec<FileRule>,
+    source_pipelines: HashMap<PathBuf, Option<LinePipeline>>,
+    container_format: Option<ContainerFormat>,
+    container_decoders: HashMap<PathBuf, ContainerLogDecoder>,
+    dedup: Option<LineDeduplicator>,
+    sampler: Option<Sampler>,
+    rate_limiter: Option<RateLimiter>,
+    keep_level: Option<Severity>,
+    alerts: Option<AlertEvaluator>,
+    renderer: LineRenderer,
+    output: Output,
+    summary: Option<Summary>,
+    matched_lines: usize,
+    max_count: Option<usize>,
+    count_only: bool,
+    quiet: bool,
+    until_match: Option<Regex>,
+    until_matched: bool,
+    timeout_at: Option<Instant>,
+    controls: Option<Controls>,
+    color_stderr: bool,
+    peer_prefix: bool,
+}
+
+impl LineEmitter {
+    pub fn new(pipeline: LinePipeline) -> Self {
+        Self {
+            pipeline,
+            file_rules: Vec::new(),
+            source_pipelines: HashMap::new(),
+            container_format: None,
+            container_decoders: HashMap::new(),
+            dedup: None,
+            sampler: None,
+            rate_limiter: None,
+            keep_level: None,
+            alerts: None,
+            renderer: LineRenderer::new(OutputFormat::Text, Vec::new()),
+            output: Output::stdout(),
+            summary: None,
+            matched_lines: 0,
+            max_count: None,
+            count_only: false,
+            quiet: false,
+            until_match: None,
+            until_matched: false,
+            timeout_at: None,
+            controls: None,
+            color_stderr: false,
+            peer_prefix: false,
+        }
+    }
+
+    pub fn with_file_rules(mut self, file_rules: Vec<FileRule>) -> Self {
+        self.file_rules = file_rules;
+        self
+    }
+
+    pub fn with_container_format(mut self, container_format: ContainerFormat) -> Self {
+        self.container_format = Some(container_format);
+        self
+    }
+
+    pub fn with_dedup(mut self, dedup: LineDeduplicator) -> Self {
+        self.dedup = Some(dedup);
+        self
+    }
+
+    pub fn with_sampler(mut self, sampler: Sampler) -> Self {
+        self.sampler = Some(sampler);
+        self
+    }
+
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    // Lines at or above this severity are never sampled out or dropped by the rate limiter.
+    pub fn with_keep_level(mut self, keep_level: Severity) -> Self {
+        self.keep_level = Some(keep_level);
+        self
+    }
+
+    pub fn with_renderer(mut self, renderer: LineRenderer) -> Self {
+        self.renderer = renderer;
+        self
+    }
+
+    pub fn with_output(mut self, output: Output) -> Self {
+        self.output = output;
+        self
+    }
+
+    pub fn with_stderr_color(mut self) -> Self {
+        self.color_stderr = true;
+        self
+    }
+
+    // Text output shows each line after the `peer` field, the address of the client that sent it.
+    fn with_peer_prefix(mut self) -> Self {
+        self.peer_prefix = true;
+        self
+    }
+
+    pub fn with_max_count(mut self, max_count: usize) -> Self {
+        self.max_count = Some(max_count);
+        self
+    }
+
+    // Lines are only counted, and run_cat prints the count per file.
+    pub fn with_count_only(mut self) -> Self {
+        self.count_only = true;
+        self
+    }
+
+    // Nothing is printed and reading stops at the first match; only the exit code tells the result.
+    pub fn with_quiet(mut self) -> Self {
+        self.quiet = true;
+        self
+    }
+
+    // Reading stops once a line matches the pattern, whether or not the filters show it, or when the
+    // timeout runs out first.
+    pub fn with_until_match(mut self, until_match: Regex, timeout: Option<Duration>) -> Self {
+        self.until_match = Some(until_match);
+        self.timeout_at = timeout.map(|timeout| Instant::now() + timeout);
+        self
+    }
+
+    pub fn with_summary(mut self) -> Self {
+        self.summary = Some(Summary::default());
+        self
+    }
+
+    pub fn with_alerts(mut self, alerts: AlertEvaluator) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    pub fn with_controls(mut self, controls: Controls) -> Self {
+        self.controls = Some(controls);
+        self
+    }
+
+    fn emit_processed_line(&mut self, source: &Path, line: &str, output_mode: OutputMode) -> Result<()> {
+        self.prepare_source_pipeline(source)?;
+        let Some(container_decoder) = self.container_decoder(source) else {
+            return self.emit_record(Some(source), line, Fields::default(), output_mode);
+        };
+
+        match container_decoder.decode(line) {
+            Some(entry) => {
+                self.emit_record(Some(source), &entry.message, entry.fields, OutputMode::AppendLineEnding)
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Partial container lines left over when a source ends are emitted as they are.
+    fn emit_partial_lines(&mut self, source: &Path) -> Result<()> {
+        let Some(container_decoder) = self.container_decoders.get_mut(source) else {
+            return Ok(());
+        };
+
+        for entry in container_decoder.finish() {
+            if self.should_stop() {
+                break;
+            }
+            self.emit_record(Some(source), &entry.message, entry.fields, OutputMode::AppendLineEnding)?;
+        }
+        Ok(())
+    }
+
+    // Files matched by file rules get their own copy of the pipeline, built when their first line arrives.
+    fn prepare_source_pipeline(&mut self, source: &Path) -> Result<()> {
+        if self.file_rules.is_empty() || self.source_pipelines.contains_key(source) {
+            return Ok(());
+        }
+
+        let file_rules: Vec<_> = self.file_rules.iter().filter(|rule| rule.matches(source)).collect();
+        let pipeline = match file_rules.is_empty() {
+            true => None,
+            false => Some(self.pipeline.with_file_rules(&file_rules)?),
+        };
+        self.source_pipelines.insert(source.to_path_buf(), pipeline);
+        Ok(())
+    }
+
+    // Lines of a memory-mapped file go through the pipeline in parallel and are emitted in order.
+    fn emit_mapped_text(&mut self, source: &Path, text: &str) -> Result<()> {
+        self.prepare_source_pipeline(source)?;
+        let pipeline = self.source_pipeline(Some(source)).clone();
+        let until_match = self.until_match.clone();
+
+        parallel_cat::process_in_order(&pipeline, until_match.as_ref(), text, |chunk| {
+            for chunk_line in chunk.lines {
+                if self.should_stop() {
+                    return Ok(false);
+                }
+                let output_mode = OutputMode::PreserveExistingLineEnding;
+                self.emit_processed_record(chunk_line.line, chunk_line.processed, output_mode)?;
+            }
+            self.until_matched |= chunk.until_matched;
+            Ok(!self.should_stop())
+        })
+    }
+
+    fn container_decoder(&mut self, source: &Path) -> Option<&mut ContainerLogDecoder> {
+        let container_format = self.container_format.or_else(|| ContainerFormat::for_path(source))?;
+
+        Some(
+            self.container_decoders
+                .entry(source.to_path_buf())
+                .or_insert_with(|| ContainerLogDecoder::new(container_format)),
+        )
+    }
+
+    fn emit_record(
+        &mut self,
+        source: Option<&Path>,
+        line: &str,
+        fields: Fields,
+        output_mode: OutputMode,
+    ) -> Result<()> {
+        if self.until_match.as_ref().is_some_and(|until_match| until_match.is_match(line)) {
+            self.until_matched = true;
+        }
+
+        if let Some(processed_line) = self.source_pipeline(source).process_record(line, fields) {
+            self.emit_processed_record(line, processed_line, output_mode)?;
+        }
+        Ok(())
+    }
+
+    fn source_pipeline(&self, source: Option<&Path>) -> &LinePipeline {
+        source
+            .and_then(|source| self.source_pipelines.get(source)?.as_ref())
+            .unwrap_or(&self.pipeline)
+    }
+
+    // Everything after the pipeline runs in line order, since it keeps state across lines.
+    fn emit_processed_record(
+        &mut self,
+        line: &str,
+        mut processed_line: ProcessedLine,
+        output_mode: OutputMode,
+    ) -> Result<()> {
+        // Excluded lines shown by --explain or --show-excluded are only printed, never counted or thinned out.
+        let shown = if processed_line.excluded {
+            !self.count_only && !self.quiet
+        } else {
+            self.keep_match(line, &processed_line.fields)?
+        };
+        if !shown {
+            return Ok(());
+        }
+
+        if self.color_stderr && processed_line.fields.get("stream") == Some("stderr") {
+            let stderr_style = TextStyle::new(Color::Red, &[]);
+            processed_line.text = Cow::Owned(apply_line_style(&processed_line.text, &stderr_style));
+        }
+        if self.peer_prefix && self.renderer.format() == OutputFormat::Text {
+            if let Some(peer) = processed_line.fields.get("peer") {
+                processed_line.text = Cow::Owned(format!("{peer} {}", processed_line.text));
+            }
+        }
+
+        match (self.renderer.format(), output_mode) {
+            (OutputFormat::Text, OutputMode::PreserveExistingLineEnding) => {
+                write!(self.output, "{}", processed_line.text)?
+            }
+            (OutputFormat::Text, OutputMode::AppendLineEnding) => writeln!(self.output, "{}", processed_line.text)?,
+            _ => writeln!(self.output, "{}", self.renderer.render_structured(line, &processed_line))?,
+        }
+
+        Ok(())
+    }
+
+    // Counts a matching line and returns whether it is shown, or dropped by -c, -q, dedup or thinning.
+    fn keep_match(&mut self, line: &str, fields: &Fields) -> Result<bool> {
+        self.matched_lines += 1;
+        if let Some(summary) = &mut self.summary {
+            summary.record(fields);
+        }
+
+        if let Some(alerts) = &mut self.alerts {
+            for alert in alerts.observe(line) {
+                alert.dispatch();
+            }
+        }
+
+        if self.count_only || self.quiet {
+            return Ok(false);
+        }
+
+        // Repeats are collapsed before any thinning, so the repeat count stays exact; sampling and rate
+        // limiting then only thin out the distinct lines.
+        let now = Instant::now();
+        if let Some(dedup) = &mut self.dedup {
+            match dedup.observe(line, now) {
+                DedupOutcome::Suppress => return Ok(false),
+                DedupOutcome::Emit { collapsed } => write_repeat_notice(&mut self.output, collapsed)?,
+            }
+        }
+
+        if let Some(rate_limiter) = &mut self.rate_limiter {
+            write_dropped_notice(&mut self.output, rate_limiter.flush_expired(now))?;
+        }
+        let kept_level = self
+            .keep_level
+            .is_some_and(|keep_level| Severity::detect(line).is_some_and(|severity| severity >= keep_level));
+        if !kept_level {
+            if self.sampler.as_mut().is_some_and(|sampler| !sampler.keep()) {
+                return Ok(false);
+            }
+            if self.rate_limiter.as_mut().is_some_and(|rate_limiter| !rate_limiter.allow(now)) {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn flush_notices(&mut self) -> Result<()> {
+        if let Some(dedup) = &mut self.dedup {
+            write_repeat_notice(&mut self.output, dedup.flush())?;
+        }
+        if let Some(rate_limiter) = &mut self.rate_limiter {
+            write_dropped_notice(&mut self.output, rate_limiter.flush())?;
+        }
+        Ok(())
+    }
+
+    fn flush_expired_notices(&mut self) -> Result<()> {
+        let now = Instant::now();
+        if let Some(dedup) = &mut self.dedup {
+            write_repeat_notice(&mut self.output, dedup.flush_expired(now))?;
+        }
+        if let Some(rate_limiter) = &mut self.rate_limiter {
+            write_dropped_notice(&mut self.output, rate_limiter.flush_expired(now))?;
+        }
+        Ok(())
+    }
+
+    // Returns whether any line matched, or with --until-match, whether its pattern was found.
+    fn finish(&mut self) -> Result<bool> {
+        self.flush_notices()?;
+        self.output.finish()?;
+        if let Some(summary) = &self.summary {
+            eprintln!("{}", summary.render());
+        }
+        Ok(self.found_match())
+    }
+
+    fn found_match(&self) -> bool {
+        match self.until_match {
+            Some(_) => self.until_matched,
+            None => self.matched_lines > 0,
+        }
+    }
+
+    fn run_command(&mut self, command: Command) -> Result<()> {
+        match command {
+            Command::Highlight(term) => {
+                self.pipeline.add_highlight_term(&term);
+                for pipeline in self.source_pipelines.values_mut().flatten() {
+                    pipeline.add_highlight_term(&term);
+                }
+            }
+            Command::Mark => {
+                self.flush_notices()?;
+                writeln!(self.output, "{}", interactive::mark_line().bold())?;
+            }
+        }
+        Ok(())
+    }
+
+    // Set once stdout or the pager has been closed, e.g. by quitting `less` or piping into `head`.
+    fn output_closed(&self) -> bool {
+        self.output.is_closed()
+    }
+
+    // Reading stops early once nobody reads the output, --max-count lines matched or the result is known.
+    fn should_stop(&self) -> bool {
+        self.output_closed()
+            || self.max_count.is_some_and(|max_count| self.matched_lines >= max_count)
+            || (self.quiet && self.until_match.is_none() && self.matched_lines > 0)
+            || self.until_matched
+    }
+
+    // When the next repeat or dropped-lines notice is due.
+    fn notice_deadline(&self) -> Option<Instant> {
+        let repeat_deadline = self.dedup.as_ref().and_then(LineDeduplicator::deadline);
+        let dropped_deadline = self.rate_limiter.as_ref().and_then(RateLimiter::deadline);
+        repeat_deadline.into_iter().chain(dropped_deadline).min()
+    }
+}
+
+pub async fn run_cat(
+    log_files: Vec<String>,
+    mut emitter: LineEmitter,
+    mut read_state: Option<ReadState>,
+) -> Result<bool> {
+    let show_file_names = log_files.len() > 1;
     for file_path in log_files {
-        let file = File::open(&file_path)
+        let mut file = File::open(&file_path)
             .await
             .with_context(|| format!("Failed to open file: {file_path}"))?;
-        let mut reader = BufReader::new(file);
-        let mut line = String::new();
+        let metadata = file.metadata().await?;
+        let mut offset = match &read_state {
+            Some(read_state) => read_state.resume_offset(&file_path, metadata.ino(), metadata.len()),
+            None => 0,
+        };
+        file.seek(SeekFrom::Start(offset)).await?;
+
+        let source = Path::new(&file_path);
+        let matched_before = emitter.matched_lines;
+        let mappable = metadata.is_file()
+            && metadata.len() > 0
+            && read_state.is_none()
+            && emitter.container_decoder(source).is_none();
+        let mapped = match mappable {
+            // SAFETY: the mapped bytes may change while they are read. Should another process truncate the
+            // file meanwhile, touching a page past its new end raises SIGBUS and kills log-viewer instead of
+            // returning an error, a risk shared with grep-like tools that map their input. Files read with
+            // --state-file are expected to still be written to, so they are never mapped.
+            true => unsafe { Mmap::map(&file) }.ok(),
+            false => None,
+        };
+        let mapped_text = mapped.as_deref().and_then(|bytes| std::str::from_utf8(bytes).ok());
+
+        match mapped_text {
+            Some(text) => emitter.emit_mapped_text(source, text)?,
+            // Pipes, container logs, files with a state file and files that are not valid UTF-8 are read
+            // line by line.
+            _ => {
+                let mut reader = BufReader::new(file);
+                let mut line = String::new();
 
-        while reader.read_line(&mut line).await? > 0 {
-            emit_processed_line(&line, &pipeline, OutputMode::PreserveExistingLineEnding)?;
-            line.clear();
+                while !emitter.should_stop() && reader.read_line(&mut line).await? > 0 {
+                    // With a state file, an unterminated last line is still being written; leave it for the next run.
+                    if read_state.is_some() && !line.ends_with('\n') {
+                        break;
+                    }
+                    offset += line.len() as u64;
+                    emitter.emit_processed_line(source, &line, OutputMode::PreserveExistingLineEnding)?;
+                    line.clear();
+                }
+            }
+        }
+
+        emitter.emit_partial_lines(source)?;
+        emitter.flush_notices()?;
+        if emitter.count_only {
+            let count = emitter.matched_lines - matched_before;
+            match show_file_names {
+                true => writeln!(emitter.output, "{file_path}:{count}")?,
+                false => writeln!(emitter.output, "{count}")?,
+            }
+        }
+        if let Some(read_state) = &mut read_state {
+            read_state.record(&file_path, metadata.ino(), offset);
         }
     }
+    let found_match = emitter.finish()?;
 
-    Ok(())
+    if let Some(read_state) = &read_state {
+        read_state.save()?;
+    }
+
+    Ok(found_match)
 }
 
-pub async fn run_watch(log_files: Vec<String>, pipeline: LinePipeline) -> Result<()> {
+pub async fn run_watch(log_files: Vec<String>, mut emitter: LineEmitter) -> Result<bool> {
     let mut log_reader = MuxedLines::new()?;
     for file in &log_files {
         log_reader
@@ -35,39 +524,442 @@ pub async fn run_watch(log_files: Vec<String>, pipeline: LinePipeline) -> Result
             .await
             .with_context(|| format!("Failed to read file: {file}"))?;
     }
+    let mut interrupt = signal(SignalKind::interrupt())?;
+
+    loop {
+        let next_line = async {
+            log_reader
+                .next_line()
+                .await
+                .with_context(|| format!("Failed while watching log files: {}", log_files.join(", ")))
+        };
+
+        match next_watch_event(&mut emitter, &mut interrupt, next_line).await? {
+            WatchEvent::Line(line) => {
+                emitter.emit_processed_line(line.source(), line.line(), OutputMode::AppendLineEnding)?
+            }
+            WatchEvent::FlushNotices => emitter.flush_expired_notices()?,
+            WatchEvent::End | WatchEvent::Interrupted | WatchEvent::Stopped => break,
+        }
+    }
 
-    while let Some(line) = log_reader
-        .next_line()
-        .await
-        .with_context(|| format!("Failed while watching log files: {}", log_files.join(", ")))?
-    {
-        emit_processed_line(line.line(), &pipeline, OutputMode::AppendLineEnding)?;
+    let sources: Vec<_> = emitter.container_decoders.keys().cloned().collect();
+    for source in sources {
+        emitter.emit_partial_lines(&source)?;
     }
+    emitter.finish()
+}
 
-    Ok(())
+pub async fn run_exec(command_line: &str, mut emitter: LineEmitter) -> Result<u8> {
+    let (mut child, mut lines) = exec::spawn(command_line)?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+
+    let stopped = loop {
+        let next_line = async {
+            Ok(tokio::select! {
+                line = lines.recv() => line.map(ExecEvent::Line),
+                _ = terminate.recv() => Some(ExecEvent::Signal(libc::SIGTERM)),
+                _ = hangup.recv() => Some(ExecEvent::Signal(libc::SIGHUP)),
+            })
+        };
+
+        match next_watch_event(&mut emitter, &mut interrupt, next_line).await? {
+            WatchEvent::Line(ExecEvent::Line(ExecLine { stream, line })) => {
+                let mut fields = Fields::default();
+                fields.insert("stream", stream);
+                emitter.emit_record(None, &line, fields, OutputMode::AppendLineEnding)?;
+            }
+            WatchEvent::Line(ExecEvent::Signal(signal)) => exec::forward_signal(&child, signal),
+            WatchEvent::FlushNotices => emitter.flush_expired_notices()?,
+            // Ctrl-C is left to the command, which decides when it ends.
+            WatchEvent::Interrupted => exec::forward_signal(&child, libc::SIGINT),
+            WatchEvent::End => break false,
+            WatchEvent::Stopped => break true,
+        }
+    };
+
+    // The exit status is always the command's, also when it was stopped early, which usually gives 143.
+    let status = match stopped {
+        true => {
+            drop(lines);
+            exec::stop(&mut child).await?
+        }
+        false => child.wait().await.context("Failed to wait for command")?,
+    };
+    emitter.finish()?;
+
+    Ok(exec::exit_code(status))
+}
+
+pub async fn run_listen(addresses: Vec<ListenAddress>, emitter: LineEmitter) -> Result<bool> {
+    let mut emitter = emitter.with_peer_prefix();
+    let (sender, mut receiver) = mpsc::channel(1024);
+    for address in &addresses {
+        listener::bind(address, sender.clone()).await?;
+    }
+    drop(sender);
+    let mut interrupt = signal(SignalKind::interrupt())?;
+
+    loop {
+        let next_line = async { Ok(receiver.recv().await) };
+
+        match next_watch_event(&mut emitter, &mut interrupt, next_line).await? {
+            WatchEvent::Line(received) => {
+                let (line, fields) = received.into_record();
+                emitter.emit_record(None, &line, fields, OutputMode::AppendLineEnding)?;
+            }
+            WatchEvent::FlushNotices => emitter.flush_expired_notices()?,
+            WatchEvent::End | WatchEvent::Interrupted | WatchEvent::Stopped => break,
+        }
+    }
+
+    emitter.finish()
+}
+
+pub async fn run_journal(options: JournalOptions, mut emitter: LineEmitter) -> Result<bool> {
+    let mut last_cursor = options.load_cursor()?;
+    let mut journalctl = options
+        .command(last_cursor.as_deref())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("Failed to start journalctl")?;
+    let stdout = journalctl.stdout.take().context("Failed to capture journalctl output")?;
+    let mut lines = BufReader::new(stdout).lines();
+    let mut interrupted = false;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+
+    loop {
+        let next_line = async { lines.next_line().await.context("Failed while reading journalctl output") };
+
+        match next_watch_event(&mut emitter, &mut interrupt, next_line).await? {
+            WatchEvent::Line(line) => {
+                let entry = JournalEntry::parse(&line)?;
+                emitter.emit_record(None, &entry.line, entry.fields, OutputMode::AppendLineEnding)?;
+                last_cursor = entry.cursor.or(last_cursor);
+            }
+            WatchEvent::FlushNotices => emitter.flush_expired_notices()?,
+            WatchEvent::End => break,
+            WatchEvent::Interrupted | WatchEvent::Stopped => {
+                interrupted = true;
+                break;
+            }
+        }
+    }
+
+    let found_match = emitter.finish()?;
+    if let Some(cursor) = &last_cursor {
+        options.save_cursor(cursor)?;
+    }
+
+    if !interrupted {
+        let status = journalctl.wait().await.context("Failed to wait for journalctl")?;
+        if !status.success() {
+            bail!("journalctl exited with {status}");
+        }
+    }
+
+    Ok(found_match)
+}
+
+enum WatchEvent<T> {
+    Line(T),
+    FlushNotices,
+    End,
+    Interrupted,
+    // Reading should end early: see should_stop, or the --timeout ran out.
+    Stopped,
+}
+
+enum ExecEvent {
+    Line(ExecLine),
+    Signal(libc::c_int),
 }
 
-fn emit_processed_line(line: &str, pipeline: &LinePipeline, output_mode: OutputMode) -> Result<()> {
-    if let Some(processed_line) = pipeline.process(line) {
-        match output_mode {
-            OutputMode::PreserveExistingLineEnding => print!("{processed_line}"),
-            OutputMode::AppendLineEnding => println!("{processed_line}"),
+// Keyboard commands are handled here, so every follow mode supports them. While a highlight term is
+// being typed, no lines are read. The interrupt stream lives as long as the follow loop, so a Ctrl-C
+// pressed while lines are being emitted is not missed.
+async fn next_watch_event<T>(
+    emitter: &mut LineEmitter,
+    interrupt: &mut Signal,
+    next_line: impl Future<Output = Result<Option<T>>>,
+) -> Result<WatchEvent<T>> {
+    tokio::pin!(next_line);
+
+    loop {
+        // Stop rather than waiting for the source to end.
+        if emitter.should_stop() {
+            return Ok(WatchEvent::Stopped);
         }
+
+        let notice_deadline = emitter.notice_deadline();
+        let flush_notices_at = notice_deadline.unwrap_or_else(Instant::now).into();
+        let timeout_at = emitter.timeout_at;
+        let prompting = emitter.controls.as_ref().is_some_and(Controls::is_prompting);
+
+        let command = tokio::select! {
+            line = &mut next_line, if !prompting => return Ok(line?.map_or(WatchEvent::End, WatchEvent::Line)),
+            _ = tokio::time::sleep_until(flush_notices_at), if notice_deadline.is_some() => {
+                return Ok(WatchEvent::FlushNotices)
+            }
+            _ = tokio::time::sleep_until(timeout_at.unwrap_or_else(Instant::now).into()), if timeout_at.is_some() => {
+                return Ok(WatchEvent::Stopped)
+            }
+            Some(command) = next_command(&mut emitter.controls) => command,
+            _ = interrupt.recv() => return Ok(WatchEvent::Interrupted),
+        };
+        emitter.run_command(command)?;
+    }
+}
+
+async fn next_command(controls: &mut Option<Controls>) -> Option<Command> {
+    match controls {
+        Some(controls) => controls.next_command().await,
+        None => std::future::pending().await,
+    }
+}
+
+fn write_repeat_notice(output: &mut Output, collapsed: Option<usize>) -> Result<()> {
+    if let Some(repeats) = collapsed {
+        writeln!(output, "{}", repeat_notice(repeats).dimmed())?;
     }
+    Ok(())
+}
 
+fn write_dropped_notice(output: &mut Output, dropped: Option<usize>) -> Result<()> {
+    if let Some(dropped) = dropped {
+        writeln!(output, "{}", dropped_notice(dropped).dimmed())?;
+    }
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{emit_processed_line, OutputMode};
-    use crate::line_pipeline::LinePipeline;
+    use super::{run_exec, LineEmitter, OutputMode};
+    use crate::container_log::ContainerFormat;
+    use crate::line_dedup::LineDeduplicator;
+    use crate::line_pipeline::{ExplainMode, LinePipeline};
+    use crate::listener::ReceivedLine;
+    use crate::sampling::{RateLimiter, Sampler};
+    use crate::severity::Severity;
+    use std::path::Path;
+    use std::time::Duration;
 
     #[test]
     fn processed_line_can_be_written_without_error() {
-        let pipeline = LinePipeline::new(None, None, true).unwrap();
+        let pipeline = LinePipeline::new(None, None, Vec::new()).unwrap();
+        let mut emitter = LineEmitter::new(pipeline);
+
+        let source = Path::new("/tmp/app.log");
+
+        assert!(emitter.emit_processed_line(source, "plain log line", OutputMode::AppendLineEnding).is_ok());
+        assert!(emitter
+            .emit_processed_line(source, "plain log line\n", OutputMode::PreserveExistingLineEnding)
+            .is_ok());
+    }
+
+    #[test]
+    fn repeated_lines_are_tracked_until_flushed() {
+        let pipeline = LinePipeline::new(None, None, Vec::new()).unwrap();
+        let mut emitter = LineEmitter::new(pipeline).with_dedup(LineDeduplicator::new(Duration::from_secs(60)));
+
+        let source = Path::new("/tmp/app.log");
+
+        emitter.emit_processed_line(source, "boom", OutputMode::AppendLineEnding).unwrap();
+        assert!(emitter.notice_deadline().is_none());
+        emitter.emit_processed_line(source, "boom", OutputMode::AppendLineEnding).unwrap();
+        assert!(emitter.notice_deadline().is_some());
+
+        emitter.flush_notices().unwrap();
+        assert!(emitter.notice_deadline().is_none());
+    }
+
+    #[test]
+    fn rate_limit_counts_dropped_lines_but_keeps_severe_ones() {
+        let pipeline = LinePipeline::new(None, None, Vec::new()).unwrap();
+        let mut emitter = LineEmitter::new(pipeline)
+            .with_rate_limiter(RateLimiter::new("1/m".parse().unwrap()))
+            .with_keep_level(Severity::Error);
+        let source = Path::new("/tmp/app.log");
+
+        emitter.emit_processed_line(source, "ERROR first", OutputMode::AppendLineEnding).unwrap();
+        emitter.emit_processed_line(source, "first", OutputMode::AppendLineEnding).unwrap();
+        assert!(emitter.notice_deadline().is_none());
+        emitter.emit_processed_line(source, "second", OutputMode::AppendLineEnding).unwrap();
+        assert!(emitter.notice_deadline().is_some());
+        emitter.emit_processed_line(source, "ERROR second", OutputMode::AppendLineEnding).unwrap();
+        assert_eq!(emitter.rate_limiter.as_mut().unwrap().flush(), Some(1));
+        assert_eq!(emitter.matched_lines, 4);
+    }
+
+    #[test]
+    fn repeats_are_counted_before_sampling() {
+        let pipeline = LinePipeline::new(None, None, Vec::new()).unwrap();
+        let mut emitter = LineEmitter::new(pipeline)
+            .with_dedup(LineDeduplicator::new(Duration::from_secs(60)))
+            .with_sampler(Sampler::new("50%".parse().unwrap()));
+        let source = Path::new("/tmp/app.log");
+
+        for _ in 0..4 {
+            emitter.emit_processed_line(source, "boom", OutputMode::AppendLineEnding).unwrap();
+        }
+        assert_eq!(emitter.dedup.as_mut().unwrap().flush(), Some(3));
+    }
+
+    #[test]
+    fn file_rules_apply_only_to_matching_sources() {
+        let pipeline = LinePipeline::new(None, None, Vec::new()).unwrap();
+        let mut emitter =
+            LineEmitter::new(pipeline).with_file_rules(vec!["app.log:-e heartbeat".parse().unwrap()]);
+        let app = Path::new("/var/log/app.log");
+        let nginx = Path::new("/var/log/nginx.log");
+
+        emitter.emit_processed_line(app, "heartbeat", OutputMode::AppendLineEnding).unwrap();
+        emitter.emit_processed_line(nginx, "heartbeat", OutputMode::AppendLineEnding).unwrap();
+
+        assert!(emitter.source_pipelines[app].as_ref().unwrap().process("heartbeat").is_none());
+        assert!(emitter.source_pipelines[nginx].is_none());
+    }
+
+    #[test]
+    fn max_count_stops_after_enough_matches() {
+        let pipeline = LinePipeline::new(None, Some(vec!["skip".to_string()]), Vec::new()).unwrap();
+        let mut emitter = LineEmitter::new(pipeline).with_max_count(2).with_count_only();
+        let source = Path::new("/tmp/app.log");
+
+        emitter.emit_processed_line(source, "one", OutputMode::AppendLineEnding).unwrap();
+        emitter.emit_processed_line(source, "skip", OutputMode::AppendLineEnding).unwrap();
+        assert!(!emitter.should_stop());
+        emitter.emit_processed_line(source, "two", OutputMode::AppendLineEnding).unwrap();
+        assert!(emitter.should_stop());
+        assert_eq!(emitter.matched_lines, 2);
+        assert!(emitter.finish().unwrap());
+    }
+
+    #[test]
+    fn mapped_text_is_emitted_until_reading_should_stop() {
+        let pipeline = LinePipeline::new(Some(vec!["GET".to_string()]), None, Vec::new()).unwrap();
+        let mut emitter = LineEmitter::new(pipeline).with_count_only();
+        let source = Path::new("/tmp/access.log");
+        let text = "skip\nGET /a\nGET /b\nready\nGET /c";
+
+        emitter.emit_mapped_text(source, text).unwrap();
+        assert_eq!(emitter.matched_lines, 3);
+
+        let pipeline = LinePipeline::new(None, None, Vec::new()).unwrap();
+        let mut emitter = LineEmitter::new(pipeline).with_count_only().with_max_count(2);
+        emitter.emit_mapped_text(source, text).unwrap();
+        assert_eq!(emitter.matched_lines, 2);
+
+        let pipeline = LinePipeline::new(None, None, Vec::new()).unwrap();
+        let until_match = regex::Regex::new("ready").unwrap();
+        let mut emitter = LineEmitter::new(pipeline).with_count_only().with_until_match(until_match, None);
+        emitter.emit_mapped_text(source, text).unwrap();
+        assert!(emitter.until_matched);
+        assert_eq!(emitter.matched_lines, 4);
+    }
+
+    #[test]
+    fn until_match_stops_on_any_line_with_the_pattern() {
+        let pipeline = LinePipeline::new(None, Some(vec!["started".to_string()]), Vec::new()).unwrap();
+        let until_match = regex::Regex::new("Server started").unwrap();
+        let mut emitter = LineEmitter::new(pipeline)
+            .with_quiet()
+            .with_until_match(until_match, Some(Duration::from_secs(60)));
+        let source = Path::new("/tmp/app.log");
+
+        emitter.emit_processed_line(source, "booting", OutputMode::AppendLineEnding).unwrap();
+        assert!(!emitter.should_stop());
+        emitter
+            .emit_processed_line(source, "Server started on :80", OutputMode::AppendLineEnding)
+            .unwrap();
+        assert!(emitter.should_stop());
+        assert!(emitter.finish().unwrap());
+    }
+
+    #[tokio::test]
+    async fn exec_stops_the_command_once_reading_should_stop() {
+        let pipeline = || LinePipeline::new(None, None, Vec::new()).unwrap();
+        let until_match = regex::Regex::new("ready").unwrap();
+
+        let max_count = run_exec("yes", LineEmitter::new(pipeline()).with_count_only().with_max_count(1));
+        let exit_code = tokio::time::timeout(Duration::from_secs(10), max_count).await.unwrap();
+        assert_eq!(exit_code.unwrap(), 128 + libc::SIGTERM as u8);
+
+        let until_match = run_exec(
+            "yes booting | head -n 1000; yes ready",
+            LineEmitter::new(pipeline()).with_quiet().with_until_match(until_match, None),
+        );
+        let exit_code = tokio::time::timeout(Duration::from_secs(10), until_match).await.unwrap();
+        assert_eq!(exit_code.unwrap(), 128 + libc::SIGTERM as u8);
+    }
+
+    #[test]
+    fn explained_excluded_lines_are_not_matches() {
+        let pipeline = LinePipeline::new(Some(vec!["GET".to_string()]), None, Vec::new())
+            .unwrap()
+            .with_explain_mode(ExplainMode::ExcludedOnly);
+        let mut emitter = LineEmitter::new(pipeline).with_max_count(1);
+        let source = Path::new("/tmp/app.log");
+
+        emitter.emit_processed_line(source, "POST /a", OutputMode::AppendLineEnding).unwrap();
+        emitter.emit_processed_line(source, "POST /b", OutputMode::AppendLineEnding).unwrap();
+        assert_eq!(emitter.matched_lines, 0);
+        assert!(!emitter.should_stop());
+        assert!(!emitter.finish().unwrap());
+    }
+
+    #[test]
+    fn quiet_stops_at_the_first_match() {
+        let pipeline = LinePipeline::new(None, None, Vec::new()).unwrap();
+        let mut emitter = LineEmitter::new(pipeline).with_quiet();
+
+        assert!(!emitter.should_stop());
+        emitter
+            .emit_processed_line(Path::new("/tmp/app.log"), "line", OutputMode::AppendLineEnding)
+            .unwrap();
+        assert!(emitter.should_stop());
+    }
+
+    #[test]
+    fn filter_words_do_not_match_the_peer_address() {
+        let pipeline = LinePipeline::new(None, Some(vec!["127.0.0".to_string()]), Vec::new()).unwrap();
+        let mut emitter = LineEmitter::new(pipeline).with_peer_prefix().with_count_only();
+        let (line, fields) = ReceivedLine {
+            peer: "127.0.0.1:40000".parse().unwrap(),
+            line: "started".to_string(),
+        }
+        .into_record();
+
+        emitter.emit_record(None, &line, fields, OutputMode::AppendLineEnding).unwrap();
+        assert_eq!(emitter.matched_lines, 1);
+    }
+
+    #[test]
+    fn container_decoders_are_kept_per_source() {
+        let pipeline = LinePipeline::new(None, None, Vec::new()).unwrap();
+        let mut emitter = LineEmitter::new(pipeline).with_container_format(ContainerFormat::Cri);
+        let first = Path::new("/var/log/containers/a.log");
+        let second = Path::new("/var/log/containers/b.log");
+
+        emitter
+            .emit_processed_line(first, "t stdout P partial", OutputMode::AppendLineEnding)
+            .unwrap();
+        emitter
+            .emit_processed_line(second, "t stdout F complete", OutputMode::AppendLineEnding)
+            .unwrap();
+
+        assert_eq!(emitter.container_decoders.len(), 2);
+        assert!(emitter.source_pipelines.is_empty());
 
-        assert!(emit_processed_line("plain log line", &pipeline, OutputMode::AppendLineEnding).is_ok());
-        assert!(emit_processed_line("plain log line\n", &pipeline, OutputMode::PreserveExistingLineEnding).is_ok());
+        let mut emitter = emitter.with_count_only();
+        emitter.emit_partial_lines(first).unwrap();
+        emitter.emit_partial_lines(second).unwrap();
+        assert_eq!(emitter.matched_lines, 2);
+        assert!(LineEmitter::new(LinePipeline::new(None, None, Vec::new()).unwrap())
+            .container_decoder(Path::new("/var/log/messages"))
+            .is_none());
     }
 }
@@ -0,0 +1,206 @@
+use anyhow::{bail, Context, Result};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::duration::parse_duration;
+
+const PERCENT_SCALE: u64 = 10_000;
+const FULL_SCALE: u64 = 100 * PERCENT_SCALE;
+
+// "200/s", "1000/m" or "50/10s": at most that many lines per period.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaxRate {
+    pub lines: usize,
+    pub period: Duration,
+}
+
+impl FromStr for MaxRate {
+    type Err = anyhow::Error;
+
+    fn from_str(rate: &str) -> Result<Self> {
+        let Some((lines, period)) = rate.split_once('/') else {
+            bail!("Invalid rate: {rate}, expected LINES/PERIOD such as 200/s");
+        };
+        let lines: usize = lines.trim().parse().with_context(|| format!("Invalid rate: {rate}"))?;
+        let period = match period.trim() {
+            period if period.starts_with(|c: char| c.is_ascii_digit()) => parse_duration(period)?,
+            unit => parse_duration(&format!("1{unit}"))?,
+        };
+        if lines == 0 || period.is_zero() {
+            bail!("Invalid rate: {rate}, lines and period must be greater than zero");
+        }
+
+        Ok(Self { lines, period })
+    }
+}
+
+// The first lines of each period are let through; the rest are dropped and counted until the period ends.
+pub struct RateLimiter {
+    max_rate: MaxRate,
+    window_start: Option<Instant>,
+    allowed: usize,
+    dropped: usize,
+}
+
+impl RateLimiter {
+    pub fn new(max_rate: MaxRate) -> Self {
+        Self {
+            max_rate,
+            window_start: None,
+            allowed: 0,
+            dropped: 0,
+        }
+    }
+
+    pub fn allow(&mut self, now: Instant) -> bool {
+        let window_ended = self
+            .window_start
+            .is_none_or(|window_start| now >= window_start + self.max_rate.period);
+        if window_ended {
+            self.window_start = Some(now);
+            self.allowed = 0;
+        }
+
+        if self.allowed < self.max_rate.lines {
+            self.allowed += 1;
+            true
+        } else {
+            self.dropped += 1;
+            false
+        }
+    }
+
+    pub fn flush(&mut self) -> Option<usize> {
+        match std::mem::take(&mut self.dropped) {
+            0 => None,
+            dropped => Some(dropped),
+        }
+    }
+
+    pub fn flush_expired(&mut self, now: Instant) -> Option<usize> {
+        match self.deadline() {
+            Some(deadline) if deadline <= now => self.flush(),
+            _ => None,
+        }
+    }
+
+    // Dropped lines are reported once their period is over.
+    pub fn deadline(&self) -> Option<Instant> {
+        match self.dropped {
+            0 => None,
+            _ => self.window_start.map(|window_start| window_start + self.max_rate.period),
+        }
+    }
+}
+
+pub fn dropped_notice(dropped: usize) -> String {
+    format!("(dropped {dropped} lines over the rate limit)")
+}
+
+// "1%" or "0.5%", kept in units of a ten-thousandth of a percent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampleRate(u64);
+
+impl FromStr for SampleRate {
+    type Err = anyhow::Error;
+
+    fn from_str(rate: &str) -> Result<Self> {
+        let percent: f64 = rate
+            .trim()
+            .trim_end_matches('%')
+            .parse()
+            .with_context(|| format!("Invalid sample rate: {rate}"))?;
+        let scaled = (percent * PERCENT_SCALE as f64).round();
+        if !(1.0..=FULL_SCALE as f64).contains(&scaled) {
+            bail!("Invalid sample rate: {rate}, expected a percentage between 0.0001% and 100%");
+        }
+
+        Ok(Self(scaled as u64))
+    }
+}
+
+// Keeps evenly spaced lines rather than random ones, so the same input always gives the same sample.
+pub struct Sampler {
+    rate: SampleRate,
+    credit: u64,
+}
+
+impl Sampler {
+    pub fn new(rate: SampleRate) -> Self {
+        // Start with enough credit to keep the first line.
+        Self {
+            rate,
+            credit: FULL_SCALE - rate.0,
+        }
+    }
+
+    pub fn keep(&mut self) -> bool {
+        self.credit += self.rate.0;
+        if self.credit < FULL_SCALE {
+            return false;
+        }
+        self.credit -= FULL_SCALE;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dropped_notice, MaxRate, RateLimiter, SampleRate, Sampler};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn parses_max_rates() {
+        assert_eq!(
+            "200/s".parse::<MaxRate>().unwrap(),
+            MaxRate {
+                lines: 200,
+                period: Duration::from_secs(1)
+            }
+        );
+        assert_eq!("50/10s".parse::<MaxRate>().unwrap().period, Duration::from_secs(10));
+        assert_eq!("1000/m".parse::<MaxRate>().unwrap().period, Duration::from_secs(60));
+        assert!("200".parse::<MaxRate>().is_err());
+        assert!("0/s".parse::<MaxRate>().is_err());
+        assert!("200/week".parse::<MaxRate>().is_err());
+    }
+
+    #[test]
+    fn drops_lines_over_the_rate_until_the_period_ends() {
+        let mut limiter = RateLimiter::new("2/s".parse().unwrap());
+        let start = Instant::now();
+
+        assert!(limiter.allow(start));
+        assert!(limiter.allow(start + Duration::from_millis(100)));
+        assert!(!limiter.allow(start + Duration::from_millis(200)));
+        assert!(!limiter.allow(start + Duration::from_millis(300)));
+
+        assert_eq!(limiter.deadline(), Some(start + Duration::from_secs(1)));
+        assert_eq!(limiter.flush_expired(start + Duration::from_millis(900)), None);
+        assert_eq!(limiter.flush_expired(start + Duration::from_secs(1)), Some(2));
+        assert_eq!(limiter.deadline(), None);
+        assert!(limiter.allow(start + Duration::from_secs(1)));
+        assert_eq!(dropped_notice(2), "(dropped 2 lines over the rate limit)");
+    }
+
+    #[test]
+    fn parses_sample_rates() {
+        assert_eq!("1%".parse::<SampleRate>().unwrap(), SampleRate(10_000));
+        assert_eq!("0.5".parse::<SampleRate>().unwrap(), SampleRate(5_000));
+        assert!("0%".parse::<SampleRate>().is_err());
+        assert!("150%".parse::<SampleRate>().is_err());
+        assert!("some".parse::<SampleRate>().is_err());
+    }
+
+    #[test]
+    fn samples_evenly_and_deterministically() {
+        let kept = |rate: &str| -> Vec<usize> {
+            let mut sampler = Sampler::new(rate.parse().unwrap());
+            (0..10).filter(|_| sampler.keep()).collect()
+        };
+
+        assert_eq!(kept("25%"), vec![0, 4, 8]);
+        assert_eq!(kept("25%"), kept("25%"));
+        assert_eq!(kept("100%").len(), 10);
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::{bail, Result};
+use regex::Regex;
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+static LEVEL_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(emerg|alert|crit|critical|fatal|panic|err|error|warn|warning|notice|info|debug|trace)\b")
+        .expect("level regex must compile")
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Trace,
+    Debug,
+    Info,
+    Warning,
+    Error,
+    Critical,
+}
+
+impl Severity {
+    pub fn detect(line: &str) -> Option<Self> {
+        LEVEL_REGEX
+            .find(line)
+            .and_then(|level| level.as_str().parse().ok())
+    }
+}
+
+impl FromStr for Severity {
+    type Err = anyhow::Error;
+
+    fn from_str(level: &str) -> Result<Self> {
+        Ok(match level.to_ascii_lowercase().as_str() {
+            "trace" => Self::Trace,
+            "debug" => Self::Debug,
+            "info" | "notice" => Self::Info,
+            "warn" | "warning" => Self::Warning,
+            "err" | "error" => Self::Error,
+            "crit" | "critical" | "fatal" | "panic" | "alert" | "emerg" => Self::Critical,
+            _ => bail!("Unknown severity level: {level}"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Severity;
+
+    #[test]
+    fn detects_first_level_word_in_line() {
+        assert_eq!(Severity::detect("2024-01-01 ERROR db down"), Some(Severity::Error));
+        assert_eq!(Severity::detect("[warn] disk almost full"), Some(Severity::Warning));
+        assert_eq!(Severity::detect("thread panic at main.rs"), Some(Severity::Critical));
+        assert_eq!(Severity::detect("errors=0 information"), None);
+    }
+
+    #[test]
+    fn orders_levels_by_severity() {
+        assert!(Severity::Critical > Severity::Error);
+        assert!(Severity::Warning > Severity::Info);
+        assert!("bogus".parse::<Severity>().is_err());
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::BTreeMap;
+
+use crate::fields::Fields;
+
+#[derive(Default)]
+pub struct Summary {
+    lines: usize,
+    status_counts: BTreeMap<String, usize>,
+}
+
+impl Summary {
+    pub fn record(&mut self, fields: &Fields) {
+        self.lines += 1;
+        if let Some(status) = fields.get("status") {
+            *self.status_counts.entry(status.to_string()).or_default() += 1;
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut rendered = format!("Lines: {}", self.lines);
+        if !self.status_counts.is_empty() {
+            rendered.push_str("\nStatus:");
+            for (status, count) in &self.status_counts {
+                rendered.push_str(&format!("\n  {status}: {count}"));
+            }
+        }
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Summary;
+    use crate::fields::Fields;
+
+    fn status(value: &str) -> Fields {
+        let mut fields = Fields::default();
+        fields.insert("status", value);
+        fields
+    }
+
+    #[test]
+    fn counts_lines_and_statuses() {
+        let mut summary = Summary::default();
+        summary.record(&status("503"));
+        summary.record(&status("200"));
+        summary.record(&status("200"));
+        summary.record(&Fields::default());
+
+        assert_eq!(summary.render(), "Lines: 4\nStatus:\n  200: 2\n  503: 1");
+    }
+
+    #[test]
+    fn omits_status_section_without_status_fields() {
+        let mut summary = Summary::default();
+        summary.record(&Fields::default());
+
+        assert_eq!(summary.render(), "Lines: 1");
+    }
+}
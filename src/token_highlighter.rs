@@ -0,0 +1,139 @@
+use clap::ValueEnum;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::line_highlighter::{Color, HighlightRule, HighlightScope, Style, TextStyle};
+
+// Listed from most to least specific: where tokens overlap, the earlier class wins, so a URL is not
+// also colored as a path and a timestamp's digits are not taken for numbers.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenClass {
+    Timestamp,
+    Url,
+    Uuid,
+    Ip,
+    Hex,
+    Number,
+    Path,
+    Quoted,
+}
+
+impl TokenClass {
+    pub const ALL: [TokenClass; 8] = [
+        TokenClass::Timestamp,
+        TokenClass::Url,
+        TokenClass::Uuid,
+        TokenClass::Ip,
+        TokenClass::Hex,
+        TokenClass::Number,
+        TokenClass::Path,
+        TokenClass::Quoted,
+    ];
+
+    fn pattern(self) -> &'static str {
+        match self {
+            TokenClass::Timestamp => {
+                r"\b\d{4}-\d{2}-\d{2}(?:[T ]\d{2}:\d{2}(?::\d{2}(?:[.,]\d+)?)?(?:Z|[+-]\d{2}:?\d{2})?)?\b"
+            }
+            TokenClass::Url => r#"\b[a-zA-Z][a-zA-Z0-9+.-]*://[^\s"'<>]*[^\s"'<>.,;:)\]]"#,
+            TokenClass::Uuid => r"\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b",
+            TokenClass::Ip => r"\b(?:\d{1,3}\.){3}\d{1,3}(?::\d{1,5})?\b",
+            TokenClass::Hex => r"\b0x[0-9a-fA-F]+\b",
+            TokenClass::Number => r"\b\d+(?:\.\d+)?(?:(?:ns|us|µs|ms|s|m|h|d|[kKMGTP]i?B|B)\b|%)",
+            // The leading character keeps "a/b" and "std::io" from counting; only the group is colored.
+            TokenClass::Path => r#"(?:^|[\s"'=(\[])((?:~|\.{1,2})?(?:/[\w.@+-]+)+/?)"#,
+            TokenClass::Quoted => r#"(?:^|[^\w'"])("(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*')"#,
+        }
+    }
+
+    fn style(self) -> TextStyle {
+        match self {
+            TokenClass::Timestamp => TextStyle::new(Color::AnsiColor(245), &[]),
+            TokenClass::Url => TextStyle::new(Color::AnsiColor(75), &[Style::Underline]),
+            TokenClass::Uuid => TextStyle::new(Color::AnsiColor(139), &[]),
+            TokenClass::Ip => TextStyle::new(Color::AnsiColor(141), &[]),
+            TokenClass::Hex => TextStyle::new(Color::AnsiColor(180), &[]),
+            TokenClass::Number => TextStyle::new(Color::AnsiColor(151), &[]),
+            TokenClass::Path => TextStyle::new(Color::AnsiColor(109), &[]),
+            TokenClass::Quoted => TextStyle::new(Color::AnsiColor(143), &[]),
+        }
+    }
+
+    fn scope(self) -> HighlightScope {
+        match self {
+            TokenClass::Path | TokenClass::Quoted => HighlightScope::Group(1),
+            _ => HighlightScope::Match,
+        }
+    }
+}
+
+pub fn token_rules(classes: &[TokenClass]) -> Vec<HighlightRule> {
+    TokenClass::ALL
+        .into_iter()
+        .filter(|class| classes.contains(class))
+        .map(|class| HighlightRule {
+            regex: Regex::new(class.pattern()).expect("token patterns are valid"),
+            style: class.style(),
+            scope: class.scope(),
+            color_by_hash: false,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{token_rules, TokenClass};
+    use crate::line_highlighter::HighlightScope;
+
+    fn tokens(class: TokenClass, line: &str) -> Vec<String> {
+        let rule = token_rules(&[class]).pop().unwrap();
+        let group = match rule.scope {
+            HighlightScope::Group(index) => index,
+            _ => 0,
+        };
+        rule.regex
+            .captures_iter(line)
+            .filter_map(|caps| caps.get(group))
+            .map(|token| token.as_str().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn recognizes_each_token_class() {
+        assert_eq!(
+            tokens(TokenClass::Timestamp, "at 2024-05-01T12:30:45.123Z and 2024-05-02 08:00:00+02:00"),
+            vec!["2024-05-01T12:30:45.123Z", "2024-05-02 08:00:00+02:00"]
+        );
+        assert_eq!(
+            tokens(TokenClass::Url, "see https://example.com/a?b=1, (http://x.io/y)"),
+            vec!["https://example.com/a?b=1", "http://x.io/y"]
+        );
+        assert_eq!(
+            tokens(TokenClass::Uuid, "req 123e4567-e89b-12d3-a456-426614174000 done"),
+            vec!["123e4567-e89b-12d3-a456-426614174000"]
+        );
+        assert_eq!(tokens(TokenClass::Ip, "from 10.0.0.12:5432 to 1.2.3"), vec!["10.0.0.12:5432"]);
+        assert_eq!(tokens(TokenClass::Hex, "fault at 0x7ffd1234 not 0xZZ"), vec!["0x7ffd1234"]);
+        assert_eq!(
+            tokens(TokenClass::Number, "took 12ms, used 3.4GB at 85% of v2s 100"),
+            vec!["12ms", "3.4GB", "85%"]
+        );
+        assert_eq!(
+            tokens(TokenClass::Path, "open /var/log/app.log file=./conf/x.toml a/b std::io"),
+            vec!["/var/log/app.log", "./conf/x.toml"]
+        );
+        assert_eq!(
+            tokens(TokenClass::Quoted, r#"msg="hello \"you\"" it's 'fine'"#),
+            vec![r#""hello \"you\"""#, "'fine'"]
+        );
+    }
+
+    #[test]
+    fn builds_rules_for_selected_classes_in_priority_order() {
+        let rules = token_rules(&[TokenClass::Number, TokenClass::Url]);
+        assert_eq!(rules.len(), 2);
+        assert!(rules[0].regex.is_match("http://x"));
+        assert_eq!(token_rules(&TokenClass::ALL).len(), TokenClass::ALL.len());
+    }
+}